@@ -0,0 +1,175 @@
+//! Animations that the turn waits on, and lenses for animating more than transforms
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use bevy_tweening::lens::Lens;
+use bevy_tweening::{component_animator_system, Animator};
+use iyes_loopless::prelude::*;
+use iyes_progress::prelude::*;
+
+/// Registers animators for the components we animate and makes the turn wait for them
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        // `bevy_tweening` only animates transforms with the features we use
+        app.add_system(component_animator_system::<Sprite>);
+        app.add_system(component_animator_system::<Text>);
+        app.add_system(component_animator_system::<DrawMode>);
+
+        app.add_system(
+            crate::utils::is_animation_done::<Transform>
+                .track_progress()
+                .run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(
+            crate::utils::is_animation_done::<Sprite>
+                .track_progress()
+                .run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(
+            crate::utils::is_animation_done::<Text>
+                .track_progress()
+                .run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(
+            crate::utils::is_animation_done::<DrawMode>
+                .track_progress()
+                .run_in_state(crate::MainState::Playing),
+        );
+
+        app.add_system(despawn_finished.run_in_state(crate::MainState::Playing));
+    }
+}
+
+/// Despawn this entity once its animations are done, used for effects like impact flashes
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct DespawnOnAnimationDone;
+
+/// Despawn effects once all their animations are done
+fn despawn_finished(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            Option<&Animator<Transform>>,
+            Option<&Animator<Sprite>>,
+            Option<&Animator<Text>>,
+            Option<&Animator<DrawMode>>,
+        ),
+        With<DespawnOnAnimationDone>,
+    >,
+) {
+    for (entity, transform, sprite, text, draw_mode) in query.iter() {
+        let progress = [
+            transform.map(Animator::progress),
+            sprite.map(Animator::progress),
+            text.map(Animator::progress),
+            draw_mode.map(Animator::progress),
+        ];
+        if progress
+            .into_iter()
+            .flatten()
+            .all(|progress| progress >= 1.)
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Fade the alpha of a sprite
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteAlphaLens {
+    /// Alpha at the start of the animation
+    pub start: f32,
+    /// Alpha at the end of the animation
+    pub end: f32,
+}
+
+impl Lens<Sprite> for SpriteAlphaLens {
+    fn lerp(&mut self, target: &mut Sprite, ratio: f32) {
+        target
+            .color
+            .set_a(self.start + (self.end - self.start) * ratio);
+    }
+}
+
+/// Tint a sprite, its alpha is left alone so it can be faded at the same time
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteTintLens {
+    /// Tint at the start of the animation
+    pub start: Color,
+    /// Tint at the end of the animation
+    pub end: Color,
+}
+
+impl Lens<Sprite> for SpriteTintLens {
+    fn lerp(&mut self, target: &mut Sprite, ratio: f32) {
+        let start = Vec4::from(self.start.as_rgba_f32());
+        let end = Vec4::from(self.end.as_rgba_f32());
+        let tint = start.lerp(end, ratio);
+        target.color = Color::rgba(tint.x, tint.y, tint.z, target.color.a());
+    }
+}
+
+/// Fade the alpha of every section of a text
+#[derive(Debug, Clone, Copy)]
+pub struct TextAlphaLens {
+    /// Alpha at the start of the animation
+    pub start: f32,
+    /// Alpha at the end of the animation
+    pub end: f32,
+}
+
+impl Lens<Text> for TextAlphaLens {
+    fn lerp(&mut self, target: &mut Text, ratio: f32) {
+        let alpha = self.start + (self.end - self.start) * ratio;
+        for section in &mut target.sections {
+            section.style.color.set_a(alpha);
+        }
+    }
+}
+
+/// Fade a filled lyon shape
+#[derive(Debug, Clone, Copy)]
+pub struct FillAlphaLens {
+    /// Color of the shape, its alpha is ignored
+    pub color: Color,
+    /// Alpha at the start of the animation
+    pub start: f32,
+    /// Alpha at the end of the animation
+    pub end: f32,
+}
+
+impl Lens<DrawMode> for FillAlphaLens {
+    fn lerp(&mut self, target: &mut DrawMode, ratio: f32) {
+        let mut color = self.color;
+        color.set_a(self.start + (self.end - self.start) * ratio);
+        *target = DrawMode::Fill(FillMode::color(color));
+    }
+}
+
+/// Push a entity away and squash it, before returning it to where it started
+#[derive(Debug, Clone, Copy)]
+pub struct KnockbackLens {
+    /// Where the entity is when not knocked back
+    pub translation: Vec3,
+    /// Scale of the entity when not squashed
+    pub scale: Vec3,
+    /// How far to push the entity at the peak of the animation
+    pub offset: Vec3,
+    /// How much to squash the entity at the peak of the animation
+    pub squash: f32,
+}
+
+impl Lens<Transform> for KnockbackLens {
+    fn lerp(&mut self, target: &mut Transform, ratio: f32) {
+        // Goes 0 -> 1 -> 0, so we end up where we started
+        let amount = (ratio * PI).sin();
+        target.translation = self.translation + self.offset * amount;
+        target.scale =
+            self.scale * Vec3::new(1. + self.squash * amount, 1. - self.squash * amount, 1.);
+    }
+}
@@ -24,13 +24,9 @@ impl Plugin for AssetLoadingPlugin {
 /// Assets for enemies
 #[derive(AssetCollection, Debug)]
 pub struct EnemyAssets {
-    /// Slime Enemy
-    #[asset(path = "Enemies/Slime.png")]
-    pub slime: Handle<Image>,
-
-    /// And orc!
-    #[asset(path = "Enemies/Orc.png")]
-    pub orc: Handle<Image>,
+    /// Enemy types, their sprites are loaded by the `EnemyRegistry`
+    #[asset(path = "Enemies.enemies.ron")]
+    pub definitions: Handle<crate::enemies::EnemyDefinitions>,
 }
 
 /// misc assets
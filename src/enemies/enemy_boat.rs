@@ -9,13 +9,14 @@ use crate::ldtk_loader::{get_tile_type_at, TileType};
 #[derive(Debug, Default, Component, Clone, Copy)]
 pub struct BoatMarker;
 
-/// Spawn boat under enemies on water
+/// Spawn boat under enemies on water, flying enemies dont need one
 pub fn spawn_despawn_boats(
     mut commnads: Commands,
     mut enemy_query: Query<
         (Entity, &Children, &GridPosition, &mut Transform),
         (
             With<super::enemy_components::EnemyMarker>,
+            Without<super::enemy_components::Flying>,
             Changed<GridPosition>,
         ),
     >,
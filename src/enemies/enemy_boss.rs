@@ -0,0 +1,248 @@
+//! Boss enemies, they change as they lose health and get a big health bar in the bottom ui
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use super::enemy_components::{EnemyArmor, EnemyHealth, EnemyMarker, EnemyPath, EnemySpeed};
+use super::enemy_systems::spawn_enemy;
+use super::enemy_types::{EnemyRegistry, EnemyType};
+use crate::grid_position::GridPosition;
+use crate::ui::BottomBarMarker;
+
+/// Width of the boss health bar
+const BAR_WIDTH: f32 = 320.;
+
+/// A stage of a boss fight
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BossPhase {
+    /// The phase starts once the health of the boss drops below this
+    pub below: u8,
+    /// What happens when the phase starts
+    pub changes: Vec<PhaseChange>,
+}
+
+/// Something that happens to a boss when a new phase starts
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum PhaseChange {
+    /// Move this many tiles each turn from now on
+    Speed(f32),
+    /// Spawn minions where the boss is, they follow the same path
+    Summon {
+        /// Name of the enemy type to spawn
+        enemy: String,
+        /// How many to spawn
+        count: u8,
+    },
+    /// Gain this much armor
+    Armor(u8),
+}
+
+/// Marker for boss enemies, keeps track of how far into the fight they are
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Boss {
+    /// How many phases have started
+    pub phase: usize,
+}
+
+/// Marker for the boss health bar in the bottom ui
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct BossBarMarker;
+
+/// Marker for the filled part of the boss health bar
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct BossBarFillMarker;
+
+/// Marker for the text on the boss health bar
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct BossBarTextMarker;
+
+/// Start the next phase of bosses that dropped below its health threshold
+///
+/// A big hit can start more than one phase at once
+pub fn advance_boss_phases(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            &mut Boss,
+            &EnemyHealth,
+            &mut EnemyArmor,
+            &mut EnemySpeed,
+            &EnemyType,
+            &EnemyPath,
+            &GridPosition,
+        ),
+        (With<EnemyMarker>, Changed<EnemyHealth>),
+    >,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+    registry: Res<EnemyRegistry>,
+) {
+    for (mut boss, health, mut armor, mut speed, enemy_type, path, position) in query.iter_mut() {
+        // Dead bosses dont get to do anything
+        if health.0 == 0 {
+            continue;
+        }
+
+        let definition = match registry.get(*enemy_type) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        let phases = definition.boss_phases().unwrap_or_default();
+        while let Some(phase) = phases
+            .get(boss.phase)
+            .filter(|phase| health.0 < phase.below)
+        {
+            boss.phase += 1;
+            info!("{} entered phase {}", definition.name, boss.phase + 1);
+
+            for change in &phase.changes {
+                match change {
+                    PhaseChange::Speed(new_speed) => speed.0 = *new_speed,
+                    PhaseChange::Armor(amount) => armor.0 = armor.0.saturating_add(*amount),
+                    PhaseChange::Summon { enemy, count } => {
+                        let minion = match registry.find(enemy) {
+                            Ok(minion) => minion,
+                            Err(err) => {
+                                error!("{err}");
+                                continue;
+                            }
+                        };
+
+                        commands
+                            .entity(world_query.single())
+                            .add_children(|parent| {
+                                for _ in 0..*count {
+                                    spawn_enemy(
+                                        parent,
+                                        &registry,
+                                        minion,
+                                        position.to_world().extend(10.),
+                                        path.clone(),
+                                        *position,
+                                    );
+                                }
+                            });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Add the hidden boss health bar to the bottom bar once it exists
+pub fn create_boss_bar(
+    mut commands: Commands,
+    bar_query: Query<Entity, Added<BottomBarMarker>>,
+    assets: Res<crate::assets::MiscAssets>,
+) {
+    for bar in bar_query.iter() {
+        commands.entity(bar).add_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(BAR_WIDTH), Val::Px(20.)),
+                        position_type: PositionType::Absolute,
+                        // Just above the bottom bar, in the middle of the screen
+                        position: UiRect {
+                            left: Val::Percent(50.),
+                            top: Val::Px(-28.),
+                            ..default()
+                        },
+                        margin: UiRect {
+                            left: Val::Px(-BAR_WIDTH / 2.),
+                            ..default()
+                        },
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        display: Display::None,
+                        ..default()
+                    },
+                    color: Color::rgba(0., 0., 0., 0.8).into(),
+                    ..default()
+                })
+                .insert(Name::new("Boss Bar"))
+                .insert(BossBarMarker)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                                position_type: PositionType::Absolute,
+                                position: UiRect {
+                                    left: Val::Px(0.),
+                                    top: Val::Px(0.),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            color: Color::rgb(0.7, 0.05, 0.05).into(),
+                            ..default()
+                        })
+                        .insert(BossBarFillMarker);
+                    parent
+                        .spawn_bundle(TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: assets.font.clone_weak(),
+                                font_size: 14.,
+                                color: Color::WHITE,
+                            },
+                        ))
+                        .insert(BossBarTextMarker);
+                });
+        });
+    }
+}
+
+/// Show the health of the boss in the bottom ui, or hide the bar when there is no boss
+///
+/// When there are multiple bosses the bar only shows one of them
+pub fn update_boss_bar(
+    bosses: Query<(Entity, &Boss, &EnemyHealth, &EnemyArmor, &EnemyType), With<EnemyMarker>>,
+    mut bar_query: Query<&mut Style, (With<BossBarMarker>, Without<BossBarFillMarker>)>,
+    mut fill_query: Query<&mut Style, (With<BossBarFillMarker>, Without<BossBarMarker>)>,
+    mut text_query: Query<&mut Text, With<BossBarTextMarker>>,
+    registry: Res<EnemyRegistry>,
+) {
+    let boss = bosses.iter().min_by_key(|(entity, ..)| *entity);
+
+    let display = if boss.is_some() {
+        Display::Flex
+    } else {
+        Display::None
+    };
+    for mut style in bar_query.iter_mut() {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+
+    let (_, boss, health, armor, enemy_type) = match boss {
+        Some(boss) => boss,
+        None => return,
+    };
+    let definition = match registry.get(*enemy_type) {
+        Some(definition) => definition,
+        None => return,
+    };
+
+    let width = Val::Percent(f32::from(health.0) / f32::from(definition.health.max(1)) * 100.);
+    for mut style in fill_query.iter_mut() {
+        if style.size.width != width {
+            style.size.width = width;
+        }
+    }
+
+    let label = format!(
+        "{}  {}/{}  Armor: {}  Phase {}",
+        definition.name,
+        health.0,
+        definition.health,
+        armor.0,
+        boss.phase + 1
+    );
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
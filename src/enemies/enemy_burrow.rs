@@ -0,0 +1,80 @@
+//! Draw burrowing enemies as a dirt mound while they travel underground
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use super::enemy_components::{Burrowed, EnemyMarker};
+use super::enemy_eyes::EyeMarker;
+use super::enemy_support::AuraMarker;
+use super::enemy_types::{EnemyRegistry, EnemyType};
+
+/// Marker for the dirt mound drawn over underground enemies
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct MoundMarker;
+
+/// Swap enemies that dig in for a dirt mound, and bring the enemy back once it surfaces
+///
+/// The mound is a child of the enemy, so it follows the enemy along the path
+pub fn update_burrowed_visuals(
+    mut commands: Commands,
+    burrowed: Query<Entity, (With<EnemyMarker>, Added<Burrowed>)>,
+    surfaced: RemovedComponents<Burrowed>,
+    mut enemies: Query<(&Children, &mut Sprite, &EnemyType), With<EnemyMarker>>,
+    mounds: Query<(), With<MoundMarker>>,
+    mut parts: Query<
+        &mut Visibility,
+        Or<(
+            With<EyeMarker>,
+            With<crate::track_bar::TrackbarProgess>,
+            With<AuraMarker>,
+        )>,
+    >,
+    registry: Res<EnemyRegistry>,
+) {
+    let changes = burrowed
+        .iter()
+        .map(|enemy| (enemy, true))
+        .chain(surfaced.iter().map(|enemy| (enemy, false)));
+    for (enemy, underground) in changes {
+        let (children, mut sprite, enemy_type) = match enemies.get_mut(enemy) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+        let scale = registry
+            .get(*enemy_type)
+            .map_or(1., |definition| definition.scale);
+        sprite.color.set_a(if underground { 0. } else { 1. });
+
+        for child in children.iter() {
+            if let Ok(mut visibility) = parts.get_mut(*child) {
+                visibility.is_visible = !underground;
+            }
+            if !underground && mounds.get(*child).is_ok() {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+
+        if underground {
+            // The mound is a child of the enemy, so undo the scale of the enemy
+            let enemy_scale = 10. / 16. * scale;
+            commands.entity(enemy).with_children(|parent| {
+                parent
+                    .spawn_bundle(GeometryBuilder::build_as(
+                        &shapes::Ellipse {
+                            radii: Vec2::new(6., 3.) / enemy_scale,
+                            center: Vec2::ZERO,
+                        },
+                        DrawMode::Outlined {
+                            fill_mode: FillMode::color(Color::rgb(0.45, 0.3, 0.15)),
+                            outline_mode: StrokeMode::new(
+                                Color::rgb(0.25, 0.15, 0.05),
+                                1. / enemy_scale,
+                            ),
+                        },
+                        Transform::from_xyz(0., -4. / enemy_scale, 1.),
+                    ))
+                    .insert(MoundMarker);
+            });
+        }
+    }
+}
@@ -3,18 +3,330 @@
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 
+use super::enemy_damage::DamageType;
+use super::enemy_status::StatusEffects;
 use super::enemy_types::EnemyType;
-use crate::{grid_position::GridPosition, utils::get_field};
+use super::enemy_waves::EnemyWaves;
+use crate::grid_position::{GridPosition, TILE_SIZE};
+use crate::ldtk_loader::{get_tile_type_at, TileType};
+use crate::utils::get_field;
 
 /// The path for enemies to follow!
 #[derive(Reflect, Default, Clone, Debug, Component)]
 #[reflect(Component)]
 pub struct EnemyPath(pub usize, pub Vec<IVec2>);
 
+impl EnemyPath {
+    /// How many tiles does an enemy at `position` still need to walk?
+    pub fn remaining_distance(&self, position: IVec2) -> i32 {
+        let mut distance = 0;
+        let mut current = position;
+        for &point in self.1.iter().skip(self.0) {
+            let diff = (point - current).abs();
+            distance += diff.x.max(diff.y);
+            current = point;
+        }
+        distance
+    }
+
+    /// Walk along the path from `position`, spending `progress` on every tile walked onto
+    ///
+    /// Calls `on_step` with the direction, the new tile and the footprint there for every step.
+    /// Flying enemies pay the same for every tile, no matter the terrain,
+    /// big enemies are [fitted](Footprint::fit) onto the path and pay for the most expensive tile
+    /// they would cover.
+    /// Returns the speed that is left over, which is nothing once the end of the path is reached.
+    pub fn walk(
+        &mut self,
+        position: &mut IVec2,
+        mut progress: f32,
+        flying: bool,
+        footprint: &mut Footprint,
+        tilemap: &LayerInstance,
+        mut on_step: impl FnMut(IVec2, IVec2, Footprint),
+    ) -> f32 {
+        while self.0 != self.1.len() {
+            let next_target_point = self.1[self.0];
+            let direction = (next_target_point - *position).clamp(IVec2::NEG_ONE, IVec2::ONE);
+
+            let next_point = *position + direction;
+            let next_footprint = if flying {
+                *footprint
+            } else {
+                footprint.fit(next_point, tilemap)
+            };
+            let cost = if flying {
+                1.
+            } else {
+                next_footprint
+                    .tiles(next_point)
+                    .map(|tile| get_tile_type_at(tilemap, tile).move_cost())
+                    .fold(0., f32::max)
+            };
+            if progress < cost {
+                break;
+            }
+            progress -= cost;
+
+            *position = next_point;
+            *footprint = next_footprint;
+            on_step(direction, next_point, next_footprint);
+
+            // Move to next point
+            if *position == next_target_point {
+                self.0 += 1;
+            }
+        }
+
+        // There is nothing left to save speed up for
+        if self.0 == self.1.len() {
+            0.
+        } else {
+            progress
+        }
+    }
+}
+
 /// How much health does this enemy have?
 #[derive(Component, Debug, Clone, Copy)]
 pub struct EnemyHealth(pub u8);
 
+/// How much armor does this enemy have left? Armor is lost before health
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct EnemyArmor(pub u8);
+
+/// Ask for damage to be dealt to an enemy
+///
+/// The actual damage done is worked out by [`calculate_damage`](super::enemy_damage::calculate_damage)
+#[derive(Debug, Clone, Copy)]
+pub struct DamageEnemy {
+    /// Enemy to damage
+    pub target: Entity,
+    /// How much health to remove
+    pub amount: u8,
+    /// What kind of damage is it?
+    pub damage_type: DamageType,
+    /// Tower or trap doing the damage, if any
+    pub source: Option<Entity>,
+}
+
+/// Sent when a enemy actually lost health or armor
+#[derive(Debug, Clone, Copy)]
+pub struct EnemyDamaged {
+    /// Enemy that was damaged
+    pub target: Entity,
+    /// How much health and armor it lost
+    pub amount: u8,
+}
+
+/// Sent when a enemy runs out of health
+#[derive(Debug, Clone, Copy)]
+pub struct EnemyKilled {
+    /// The enemy that died, it is despawned once its death animation is done
+    pub entity: Entity,
+    /// What type of enemy was it?
+    pub enemy_type: EnemyType,
+    /// Where did it die?
+    pub position: GridPosition,
+    /// Tower or trap that did the killing blow, if any
+    pub killer: Option<Entity>,
+}
+
+/// Sent for every tile a enemy walks onto, a enemy can walk over multiple tiles in one move
+#[derive(Debug, Clone, Copy)]
+pub struct EnemyStepped {
+    /// The enemy that moved
+    pub enemy: Entity,
+    /// Tile it walked onto
+    pub position: GridPosition,
+}
+
+/// How many tiles does this enemy move each turn?
+///
+/// Starts out as the speed of the enemy type, bosses can change it when a new phase starts
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct EnemySpeed(pub f32);
+
+/// How many tiles wide and high is this enemy? Big enemies like bosses cover a square of tiles
+///
+/// The [`GridPosition`] of the enemy is the path tile it stands on, the square always covers it.
+/// Where the rest of the square goes is picked again on every step, see [`Footprint::fit`]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Footprint {
+    /// Width and height of the square in tiles
+    pub size: u8,
+    /// Offset from the tile the enemy stands on to the bottom left tile of the square
+    pub offset: IVec2,
+}
+
+impl Default for Footprint {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl Footprint {
+    /// A square of `size` tiles, centered on the tile the enemy stands on as far as it can be
+    pub fn new(size: u8) -> Self {
+        let size = size.max(1);
+        Self {
+            size,
+            offset: -IVec2::splat(i32::from((size - 1) / 2)),
+        }
+    }
+
+    /// Every tile covered by a enemy standing on `anchor`
+    pub fn tiles(self, anchor: IVec2) -> impl Iterator<Item = IVec2> {
+        let size = i32::from(self.size);
+        let start = anchor + self.offset;
+        (0..size).flat_map(move |x| (0..size).map(move |y| start + IVec2::new(x, y)))
+    }
+
+    /// Does a enemy standing on `anchor` cover `tile`?
+    pub fn covers(self, anchor: IVec2, tile: IVec2) -> bool {
+        let offset = tile - (anchor + self.offset);
+        offset.cmpge(IVec2::ZERO).all() && offset.cmplt(IVec2::splat(i32::from(self.size))).all()
+    }
+
+    /// Move the square around `anchor` so it covers as many path and water tiles as it can
+    ///
+    /// This keeps big enemies from hanging over the grass and towers next to the path.
+    /// When several places are as good the enemy keeps its current one, or else takes the one
+    /// closest to the middle.
+    pub fn fit(self, anchor: IVec2, tilemap: &LayerInstance) -> Self {
+        let size = i32::from(self.size);
+        let centered = Self::new(self.size).offset;
+        (0..size)
+            .flat_map(|x| (0..size).map(move |y| -IVec2::new(x, y)))
+            .map(|offset| Self { offset, ..self })
+            .max_by_key(|footprint| {
+                let walkable = footprint
+                    .tiles(anchor)
+                    .filter(|tile| {
+                        matches!(
+                            get_tile_type_at(tilemap, *tile),
+                            TileType::Path | TileType::Water
+                        )
+                    })
+                    .count();
+                let from_center = (footprint.offset - centered).abs();
+                (
+                    walkable,
+                    footprint.offset == self.offset,
+                    -(from_center.x + from_center.y),
+                )
+            })
+            .unwrap_or(self)
+    }
+
+    /// Distance from `from` to the closest tile covered by a enemy standing on `anchor`
+    pub fn distance(self, anchor: GridPosition, from: GridPosition) -> f32 {
+        self.tiles(anchor.0)
+            .map(|tile| from.distance(GridPosition(tile)))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Tiles between `from` and the closest tile covered by a enemy standing on `anchor`
+    pub fn tiles_between(self, anchor: GridPosition, from: GridPosition) -> u32 {
+        self.tiles(anchor.0)
+            .map(|tile| from.tiles_between(GridPosition(tile)))
+            .min()
+            .unwrap_or(u32::MAX)
+    }
+
+    /// Offset from the center of the anchor tile to the center of the covered area
+    pub fn center_offset(self) -> Vec2 {
+        Vec2::splat(f32::from(self.size.saturating_sub(1)) * TILE_SIZE / 2.)
+            + self.offset.as_vec2() * TILE_SIZE
+    }
+}
+
+/// Speed a enemy has saved up but not used yet
+///
+/// Every move the enemy gains its speed and spends it on tiles, slow enemies need a few turns to
+/// save up enough for a single tile
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct MoveProgress(pub f32);
+
+/// Marker for flying enemies, they ignore terrain and traps and only anti air towers can hit them
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Flying;
+
+/// Marker for the shadow drawn below flying enemies
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct FlyingShadowMarker;
+
+/// Marker for stealthy enemies, they are [`Hidden`] unless a detection tower can see them or they are [`Revealed`]
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Stealth;
+
+/// Marker for stealthy enemies that are not detected right now, towers can not target them
+///
+/// Detection is recomputed at the start of every player turn
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Hidden;
+
+/// Reveals a stealthy enemy wherever it is, for spells and other effects that dont need a detection tower
+///
+/// Holds how many player turns the enemy stays revealed, counting the one it was revealed in
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Revealed(pub u8);
+
+/// Where a burrowing enemy is in its trip underground, counted in tiles walked
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Burrow {
+    /// Tiles left to walk above ground before digging in
+    pub before: u8,
+    /// Tiles left to walk underground before surfacing
+    pub underground: u8,
+}
+
+impl Burrow {
+    /// Is the enemy underground right now?
+    pub fn is_underground(self) -> bool {
+        self.before == 0 && self.underground > 0
+    }
+
+    /// Count a tile walked, digging in or surfacing once enough tiles have been walked
+    pub fn step(&mut self) {
+        if self.before > 0 {
+            self.before -= 1;
+        } else {
+            self.underground = self.underground.saturating_sub(1);
+        }
+    }
+}
+
+/// Marker for burrowing enemies that are underground, towers and traps can not touch them
+///
+/// Their [`GridPosition`] keeps moving along the path, so they surface where the move preview shows
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Burrowed;
+
+/// Makes a enemy tougher than its definition, used by the waves of endless mode
+///
+/// Applied once when the enemy spawns
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct EnemyScaling {
+    /// Health is multiplied by this
+    pub health: f32,
+    /// Speed is multiplied by this
+    pub speed: f32,
+}
+
+impl Default for EnemyScaling {
+    fn default() -> Self {
+        Self {
+            health: 1.,
+            speed: 1.,
+        }
+    }
+}
+
+/// Marker for enemies playing their death animation, they no longer have a [`EnemyMarker`]
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Dying;
+
 /// All enmies will have this components
 #[derive(Component, Default)]
 pub struct EnemyMarker;
@@ -33,7 +345,7 @@ pub struct EnemyBundle {
 
     // /// Holds a list of transforms that will be combined into the final position
     // pub stacked_transform: StackedTransforms,
-    /// Path enemy needs to take
+    /// Path enemy needs to take, flying enemies only get the end of it
     pub path: EnemyPath,
 
     /// Location of enemy in grid, updated by move system
@@ -42,6 +354,21 @@ pub struct EnemyBundle {
     /// How much health we got?
     pub health: EnemyHealth,
 
+    /// Armor protecting the health
+    pub armor: EnemyArmor,
+
+    /// Effects like poison and freeze on the enemy
+    pub status_effects: StatusEffects,
+
+    /// Tiles moved each turn
+    pub speed: EnemySpeed,
+
+    /// Speed saved up from earlier turns
+    pub move_progress: MoveProgress,
+
+    /// Tiles covered by the enemy
+    pub footprint: Footprint,
+
     /// What type is this enemy?
     pub enemy_type: EnemyType,
 }
@@ -55,28 +382,17 @@ impl Default for EnemyBundle {
             path: EnemyPath(0, Vec::new()),
             grid_location: GridPosition::default(),
             health: EnemyHealth(10),
+            armor: EnemyArmor::default(),
+            status_effects: StatusEffects::default(),
+            speed: EnemySpeed(1.),
+            move_progress: MoveProgress::default(),
+            footprint: Footprint::default(),
             // This will be overwritten when a enemy is spawned, but we need a default!
-            enemy_type: EnemyType::Slime,
+            enemy_type: EnemyType::default(),
         }
     }
 }
 
-/// Spawner order of enemies
-#[derive(Default, Clone, Debug, Component)]
-pub struct EnemyWaves(pub usize, pub Vec<Option<EnemyType>>);
-
-impl From<EntityInstance> for EnemyWaves {
-    fn from(instance: EntityInstance) -> Self {
-        let waves = extract!(get_field(&instance, "EnemyType"), FieldValue::Enums(vals) => vals);
-        let waves = waves
-            .iter()
-            .map(|value| value.as_ref().map(EnemyType::from))
-            .collect();
-
-        EnemyWaves(0, waves)
-    }
-}
-
 /// Mark the enemy spawners
 #[derive(Debug, Default, Component)]
 pub struct EnemySpawner;
@@ -156,3 +472,73 @@ impl LdtkEntity for EnemySpawnerBundle {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tilemap one tile high, 3 is path and 2 is water
+    fn tilemap(row: &[i32]) -> LayerInstance {
+        LayerInstance {
+            c_hei: 1,
+            c_wid: row.len() as i32,
+            grid_size: 16,
+            identifier: "Tiles".to_owned(),
+            opacity: 1.,
+            px_total_offset_x: 0,
+            px_total_offset_y: 0,
+            tileset_def_uid: None,
+            tileset_rel_path: None,
+            layer_instance_type: bevy_ecs_ldtk::ldtk::Type::IntGrid,
+            auto_layer_tiles: Vec::new(),
+            entity_instances: Vec::new(),
+            grid_tiles: Vec::new(),
+            iid: String::new(),
+            int_grid: None,
+            int_grid_csv: row.to_vec(),
+            layer_def_uid: 0,
+            level_id: 0,
+            optional_rules: Vec::new(),
+            override_tileset_uid: None,
+            px_offset_x: 0,
+            px_offset_y: 0,
+            seed: 0,
+            visible: true,
+        }
+    }
+
+    /// Walk a straight path to `end` from the first tile, returning where the walk stopped
+    fn walk(tilemap: &LayerInstance, end: i32, progress: f32, flying: bool) -> (IVec2, f32) {
+        let mut path = EnemyPath(0, vec![IVec2::new(end, 0)]);
+        let mut position = IVec2::ZERO;
+        let left = path.walk(
+            &mut position,
+            progress,
+            flying,
+            &mut Footprint::default(),
+            tilemap,
+            |_, _, _| {},
+        );
+        (position, left)
+    }
+
+    #[test]
+    fn water_costs_more() {
+        let tilemap = tilemap(&[3, 2, 3, 3, 3]);
+        assert_eq!(walk(&tilemap, 4, 1.5, false), (IVec2::ZERO, 1.5));
+        assert_eq!(walk(&tilemap, 4, 2.5, false), (IVec2::new(1, 0), 0.5));
+        assert_eq!(walk(&tilemap, 4, 3., false), (IVec2::new(2, 0), 0.));
+    }
+
+    #[test]
+    fn flying_ignores_water() {
+        let tilemap = tilemap(&[3, 2, 3, 3, 3]);
+        assert_eq!(walk(&tilemap, 4, 2., true), (IVec2::new(2, 0), 0.));
+    }
+
+    #[test]
+    fn nothing_is_saved_at_the_end_of_the_path() {
+        let tilemap = tilemap(&[3, 3, 3, 3, 3]);
+        assert_eq!(walk(&tilemap, 4, 10., false), (IVec2::new(4, 0), 0.));
+    }
+}
@@ -0,0 +1,159 @@
+//! Damage types, armor and resistances
+//!
+//! All damage done to enemies goes through [`calculate_damage`], so balancing only has to happen in one place
+
+use serde::Deserialize;
+
+use super::enemy_status::{StatusEffects, StatusKind};
+use super::enemy_types::EnemyDefinition;
+
+/// What kind of damage is this?
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum DamageType {
+    /// Arrows, cannon balls and spikes, blocked by armor
+    Physical,
+    /// Goes straight through armor
+    Magic,
+    /// Explosions and burning
+    Fire,
+    /// Freezing water
+    Ice,
+}
+
+impl DamageType {
+    /// Name shown to the player
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Physical => "Physical",
+            Self::Magic => "Magic",
+            Self::Fire => "Fire",
+            Self::Ice => "Ice",
+        }
+    }
+}
+
+/// How much a hit takes off a enemy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DamageDealt {
+    /// Armor lost
+    pub armor: u8,
+    /// Health lost
+    pub health: u8,
+}
+
+/// Work out how much damage a hit does to a enemy
+///
+/// Weaken adds to the damage first, then the resistance of the enemy type scales it.
+/// Armor soaks up what is left before health does, except for magic damage which ignores it.
+/// Enemies whose type was removed from the definitions dont resist anything.
+pub fn calculate_damage(
+    amount: u8,
+    damage_type: DamageType,
+    definition: Option<&EnemyDefinition>,
+    armor: u8,
+    health: u8,
+    effects: &StatusEffects,
+) -> DamageDealt {
+    let weaken = effects
+        .get(StatusKind::Weaken)
+        .map_or(0, |weaken| weaken.strength);
+    let resistance = definition
+        .and_then(|definition| definition.resistances.get(&damage_type).copied())
+        .unwrap_or(1.);
+    let amount = (f32::from(amount.saturating_add(weaken)) * resistance)
+        .round()
+        .clamp(0., f32::from(u8::MAX)) as u8;
+
+    let armor_lost = if damage_type == DamageType::Magic {
+        0
+    } else {
+        amount.min(armor)
+    };
+    DamageDealt {
+        armor: armor_lost,
+        health: (amount - armor_lost).min(health),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enemies::StatusEffect;
+
+    /// A plain enemy type with these resistances
+    fn definition(resistances: &[(DamageType, f32)]) -> EnemyDefinition {
+        EnemyDefinition {
+            name: "Slime".to_owned(),
+            sprite: String::new(),
+            health: 10,
+            armor: 0,
+            resistances: resistances.iter().copied().collect(),
+            eyes: Vec::new(),
+            scale: 1.,
+            size: 1,
+            speed: 1.,
+            bounty: 0,
+            leak_damage: 1,
+            abilities: Vec::new(),
+            texture: Default::default(),
+        }
+    }
+
+    #[test]
+    fn armor_soaks_up_damage_first() {
+        let effects = StatusEffects::default();
+        let dealt = calculate_damage(5, DamageType::Physical, None, 3, 10, &effects);
+        assert_eq!(
+            dealt,
+            DamageDealt {
+                armor: 3,
+                health: 2
+            }
+        );
+    }
+
+    #[test]
+    fn magic_ignores_armor() {
+        let effects = StatusEffects::default();
+        let dealt = calculate_damage(5, DamageType::Magic, None, 3, 10, &effects);
+        assert_eq!(
+            dealt,
+            DamageDealt {
+                armor: 0,
+                health: 5
+            }
+        );
+    }
+
+    #[test]
+    fn damage_stops_at_the_health_left() {
+        let effects = StatusEffects::default();
+        let dealt = calculate_damage(5, DamageType::Physical, None, 0, 2, &effects);
+        assert_eq!(dealt.health, 2);
+    }
+
+    #[test]
+    fn resistance_scales_damage() {
+        let effects = StatusEffects::default();
+        let slime = definition(&[(DamageType::Fire, 0.5), (DamageType::Ice, 2.)]);
+        let damage =
+            |damage_type| calculate_damage(5, damage_type, Some(&slime), 0, 20, &effects).health;
+        // Halves round to the nearest whole damage
+        assert_eq!(damage(DamageType::Fire), 3);
+        assert_eq!(damage(DamageType::Ice), 10);
+        assert_eq!(damage(DamageType::Physical), 5);
+    }
+
+    #[test]
+    fn weaken_adds_before_resistance() {
+        let slime = definition(&[(DamageType::Fire, 0.5)]);
+        let mut effects = StatusEffects::default();
+        effects.add(StatusEffect {
+            kind: StatusKind::Weaken,
+            strength: 3,
+            turns: 2,
+        });
+        let dealt = calculate_damage(5, DamageType::Fire, Some(&slime), 0, 20, &effects);
+        assert_eq!(dealt.health, 4);
+    }
+}
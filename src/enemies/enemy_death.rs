@@ -0,0 +1,153 @@
+//! Play a death animation for killed enemies before removing them
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_tweening::lens::TransformScaleLens;
+use bevy_tweening::{Animator, EaseFunction, Tween, TweeningType};
+
+use super::enemy_components::{Dying, EnemyKilled, EnemyMarker, EnemyPath};
+use super::enemy_endless::EndlessMode;
+use super::enemy_eyes::EyeMarker;
+use super::enemy_systems::spawn_enemy;
+use super::enemy_types::{EnemyAbility, EnemyRegistry};
+use super::enemy_waves::EnemyWaves;
+use crate::animation::{DespawnOnAnimationDone, SpriteAlphaLens};
+
+/// How long it takes a dead enemy to shrink away
+const DEATH_DURATION: Duration = Duration::from_millis(300);
+
+/// How long it takes the eyes to close, this happens at the same time as the shrinking
+const EYE_CLOSE_DURATION: Duration = Duration::from_millis(150);
+
+/// Shrink and fade killed enemies, closing their eyes
+///
+/// The enemy loses its marker right away so towers and other enemies ignore it,
+/// it is despawned once the animation is done.
+pub fn start_death_animation(
+    mut commands: Commands,
+    mut events: EventReader<EnemyKilled>,
+    query: Query<(&Transform, &Children), With<EnemyMarker>>,
+    eye_query: Query<&Transform, With<EyeMarker>>,
+) {
+    for event in events.iter() {
+        let (transform, children) = match query.get(event.entity) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+
+        commands
+            .entity(event.entity)
+            .remove::<EnemyMarker>()
+            .insert(Dying)
+            .insert(DespawnOnAnimationDone)
+            .insert(Animator::new(Tween::new(
+                EaseFunction::BackIn,
+                TweeningType::Once,
+                DEATH_DURATION,
+                TransformScaleLens {
+                    start: transform.scale,
+                    end: Vec3::new(0., 0., 1.),
+                },
+            )))
+            .insert(Animator::new(Tween::new(
+                EaseFunction::QuadraticIn,
+                TweeningType::Once,
+                DEATH_DURATION,
+                SpriteAlphaLens { start: 1., end: 0. },
+            )));
+
+        for child in children.iter() {
+            if let Ok(eye) = eye_query.get(*child) {
+                commands.entity(*child).insert(Animator::new(Tween::new(
+                    EaseFunction::QuadraticOut,
+                    TweeningType::Once,
+                    EYE_CLOSE_DURATION,
+                    TransformScaleLens {
+                        start: eye.scale,
+                        end: Vec3::new(eye.scale.x, 0.1, 1.),
+                    },
+                )));
+            }
+        }
+    }
+}
+
+/// Run the on death abilities of killed enemies, like slimes splitting into smaller slimes
+///
+/// Spawned enemies start where the enemy died and continue along the rest of its path
+pub fn spawn_on_death(
+    mut commands: Commands,
+    mut events: EventReader<EnemyKilled>,
+    query: Query<&EnemyPath>,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+    registry: Res<EnemyRegistry>,
+) {
+    for event in events.iter() {
+        let path = match query.get(event.entity) {
+            Ok(path) => path,
+            Err(_) => continue,
+        };
+
+        for ability in registry.abilities(event.enemy_type) {
+            match ability {
+                EnemyAbility::Flying
+                | EnemyAbility::Stealth
+                | EnemyAbility::Burrow { .. }
+                | EnemyAbility::Healer { .. }
+                | EnemyAbility::Shielder { .. }
+                | EnemyAbility::Summoner { .. }
+                | EnemyAbility::AttackTowers { .. }
+                | EnemyAbility::StunTowers { .. }
+                | EnemyAbility::StealGold { .. }
+                | EnemyAbility::Boss { .. } => {}
+                EnemyAbility::SpawnOnDeath { enemy, count } => {
+                    let enemy_type = match registry.find(enemy) {
+                        Ok(enemy_type) => enemy_type,
+                        Err(err) => {
+                            error!("{err}");
+                            continue;
+                        }
+                    };
+
+                    commands
+                        .entity(world_query.single())
+                        .add_children(|parent| {
+                            for _ in 0..*count {
+                                spawn_enemy(
+                                    parent,
+                                    &registry,
+                                    enemy_type,
+                                    event.position.to_world().extend(10.),
+                                    path.clone(),
+                                    event.position,
+                                );
+                            }
+                        });
+                }
+            }
+        }
+    }
+}
+
+/// Has every spawner run out of waves and every enemy been dealt with?
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WavesCleared(pub bool);
+
+/// Check if the level is won
+///
+/// Dying enemies still count, since they might spawn more enemies when their death is handled.
+/// In endless mode the waves never run out, so the level is never won.
+pub fn check_waves_cleared(
+    spawners: Query<&EnemyWaves>,
+    enemies: Query<(), Or<(With<EnemyMarker>, With<Dying>)>>,
+    endless: Res<EndlessMode>,
+    mut cleared: ResMut<WavesCleared>,
+) {
+    let spawning_done = spawners.iter().all(EnemyWaves::is_done);
+    let is_cleared =
+        !endless.enabled && !spawners.is_empty() && spawning_done && enemies.is_empty();
+    if cleared.0 != is_cleared {
+        cleared.0 = is_cleared;
+    }
+}
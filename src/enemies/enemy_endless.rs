@@ -0,0 +1,427 @@
+//! Endless mode, new waves are made up once the waves of a level run out
+//!
+//! Whenever every spawner is done, each one gets a generated wave built from the known enemy types.
+//! A wave gets a budget that grows with every turn survived, and its enemies get more health,
+//! speed and elites as the run goes on. The waves are seeded, so the same seed always plays the
+//! same. Start the game with `--seed <hex>` to play a seed from the ui again, otherwise a new one
+//! is picked.
+//!
+//! Endless mode can only be toggled before the first turn of a level ends, so a run is played
+//! with the same mode from start to end. The most turns survived on each level is saved once the
+//! run ends, in [`HIGH_SCORE_FILE`] in the data directory of the user.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use super::enemy_components::{EnemyArmor, EnemyHealth, EnemyScaling, EnemySpawner, EnemySpeed};
+use super::enemy_types::{EnemyDefinition, EnemyRegistry};
+use super::enemy_waves::{EnemyWaves, Wave, WAVE_PAUSE};
+use crate::grid_position::GridPosition;
+use crate::player::PlayerHealth;
+
+/// File the best endless runs are kept in, one `Level=turns` line per level
+const HIGH_SCORE_FILE: &str = "highscores.txt";
+
+/// Folder in the data directory of the user the high scores are saved in
+const DATA_FOLDER: &str = "turn_td";
+
+/// Budget of the first generated wave
+const BASE_BUDGET: f32 = 8.;
+
+/// Budget added to a wave for every turn survived
+const BUDGET_PER_TURN: f32 = 0.6;
+
+/// Should new waves be made up once the level runs out, and from which seed?
+#[derive(Debug, Clone, Copy)]
+pub struct EndlessMode {
+    /// Toggled with E before the first turn ends
+    pub enabled: bool,
+    /// The same seed gives the same waves, set with `--seed`
+    pub seed: u64,
+}
+
+impl Default for EndlessMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seed: seed_from_args().unwrap_or_else(new_seed),
+        }
+    }
+}
+
+/// The seed passed with `--seed <hex>`, written like the ui shows it
+fn seed_from_args() -> Option<u64> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--seed").skip(1);
+    let text = args.next()?;
+    let seed = u64::from_str_radix(text.trim_start_matches("0x"), 16).ok();
+    if seed.is_none() {
+        warn!("Invalid seed `{text}`, picking a new one");
+    }
+    seed
+}
+
+/// A new seed for every time the game is started
+fn new_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64)
+}
+
+/// How far the player got in the current endless run
+#[derive(Debug, Default, Clone)]
+pub struct EndlessRun {
+    /// Turns survived with endless mode on
+    pub turns: u32,
+    /// Generated waves so far, every spawner gets one each time
+    pub waves: u32,
+    /// Most turns ever survived on this level
+    pub best: u32,
+    /// Has the first turn ended? Endless mode cant be toggled after that
+    pub locked: bool,
+    /// Level the run is on, `None` if it could not be found
+    pub level: Option<String>,
+}
+
+/// Most turns survived in endless mode, by level identifier
+#[derive(Debug, Default, Clone)]
+pub struct HighScores(pub HashMap<String, u32>);
+
+/// Where the high scores are saved, `None` if the user has no data directory
+fn high_score_path() -> Option<PathBuf> {
+    let data_dir = match std::env::var_os("APPDATA") {
+        Some(dir) => PathBuf::from(dir),
+        None => match std::env::var_os("XDG_DATA_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+        },
+    };
+    Some(data_dir.join(DATA_FOLDER).join(HIGH_SCORE_FILE))
+}
+
+impl HighScores {
+    /// Read the high scores, a missing file just means nothing was played yet
+    pub fn load() -> Self {
+        let text = match high_score_path().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(text) => text,
+            None => return Self::default(),
+        };
+        let scores = text
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .filter_map(|(level, turns)| {
+                Some((level.trim().to_owned(), turns.trim().parse().ok()?))
+            })
+            .collect();
+        Self(scores)
+    }
+
+    /// Write the high scores back to disk
+    pub fn save(&self) {
+        let path = match high_score_path() {
+            Some(path) => path,
+            None => {
+                warn!("Could not save high scores: no data directory");
+                return;
+            }
+        };
+        let mut levels: Vec<_> = self.0.iter().collect();
+        levels.sort();
+        let text: String = levels
+            .iter()
+            .map(|(level, turns)| format!("{level}={turns}\n"))
+            .collect();
+        let saved = match path.parent() {
+            Some(dir) => fs::create_dir_all(dir).and_then(|()| fs::write(&path, text)),
+            None => fs::write(&path, text),
+        };
+        if let Err(err) = saved {
+            warn!("Could not save high scores to {}: {err}", path.display());
+        }
+    }
+
+    /// Keep the run if it is the best one on its level, saving only when something changed
+    fn record(&mut self, run: &EndlessRun) {
+        let level = match &run.level {
+            Some(level) => level,
+            None => return,
+        };
+        if run.turns <= self.0.get(level).copied().unwrap_or(0) {
+            return;
+        }
+        self.0.insert(level.clone(), run.turns);
+        self.save();
+    }
+}
+
+/// Small seeded random number generator, SplitMix64
+struct WaveRng(u64);
+
+impl WaveRng {
+    /// A generator for one wave of one spawner
+    fn new(seed: u64, wave: u32, spawner: usize) -> Self {
+        let mut rng = Self(seed ^ (u64::from(wave) << 32) ^ spawner as u64);
+        // Mix the parts together, so close seeds dont give close waves
+        rng.next_u64();
+        rng
+    }
+
+    /// The next random number
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number between 0 and 1
+    fn chance(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Pick one of the items, `None` if there are none
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        items.get((self.next_u64() % items.len() as u64) as usize)
+    }
+}
+
+/// How dangerous a enemy is, this is what it costs from the budget of a wave
+fn threat(definition: &EnemyDefinition) -> f32 {
+    (f32::from(definition.health) + 2. * f32::from(definition.armor)) * definition.speed.max(0.5)
+}
+
+/// Enemies of the group that fit in what is left of the budget
+fn affordable<'a>(group: &[(&'a str, f32)], budget: f32) -> Vec<(&'a str, f32)> {
+    group
+        .iter()
+        .filter(|(_, threat)| *threat <= budget)
+        .copied()
+        .collect()
+}
+
+/// Toggle endless mode when E is pressed, until the first turn ends
+pub fn toggle_endless_mode(
+    keys: Res<Input<KeyCode>>,
+    mut endless: ResMut<EndlessMode>,
+    run: Res<EndlessRun>,
+) {
+    if keys.just_pressed(KeyCode::E) {
+        if run.locked {
+            info!("Endless mode can only be changed before the first turn ends");
+            return;
+        }
+        endless.enabled = !endless.enabled;
+        info!(
+            "Endless mode {}",
+            if endless.enabled { "on" } else { "off" }
+        );
+    }
+}
+
+/// Start a new run when we enter gameplay
+pub fn reset_endless_run(
+    mut commands: Commands,
+    scores: Res<HighScores>,
+    assets: Res<crate::assets::MiscAssets>,
+    asset_store: Res<Assets<LdtkAsset>>,
+    current_level: Res<LevelSelection>,
+) {
+    let level = level_identifier(&assets, &asset_store, &current_level);
+    if level.is_none() {
+        warn!("Could not find the current level, endless runs wont get a high score");
+    }
+    commands.insert_resource(EndlessRun {
+        best: level
+            .as_ref()
+            .and_then(|level| scores.0.get(level).copied())
+            .unwrap_or(0),
+        level,
+        ..default()
+    });
+}
+
+/// Keep endless mode as it is for the rest of the level once the first turn ends
+pub fn lock_endless_mode(mut run: ResMut<EndlessRun>) {
+    if !run.locked {
+        run.locked = true;
+    }
+}
+
+/// Give every spawner a new wave once they have all sent their last one
+///
+/// This runs at the start of the player turn, so the new wave shows up in the preview
+/// during the pause it starts with.
+pub fn generate_endless_waves(
+    endless: Res<EndlessMode>,
+    mut run: ResMut<EndlessRun>,
+    mut spawners: Query<(&mut EnemyWaves, &GridPosition), With<EnemySpawner>>,
+    registry: Res<EnemyRegistry>,
+) {
+    if !endless.enabled || spawners.is_empty() || !spawners.iter().all(|(waves, _)| waves.is_done())
+    {
+        return;
+    }
+
+    // Bosses have their own big entrance, they dont fit in a generated wave
+    let mut pool: Vec<(&str, f32)> = registry
+        .types()
+        .filter_map(|enemy_type| registry.get(enemy_type))
+        .filter(|definition| definition.boss_phases().is_none())
+        .map(|definition| (definition.name.as_str(), threat(definition)))
+        .filter(|(_, threat)| *threat > 0.)
+        .collect();
+    if pool.is_empty() {
+        return;
+    }
+    pool.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    // The most dangerous third are the elites
+    let elites = pool.split_off(pool.len() - (pool.len() / 3).max(1));
+
+    run.waves += 1;
+    let turns = run.turns as f32;
+    let budget = BASE_BUDGET + BUDGET_PER_TURN * turns;
+    let elite_chance = (0.1 + 0.02 * turns).min(0.6);
+    let stack_chance = (0.1 + 0.01 * turns).min(0.5);
+    let scaling = EnemyScaling {
+        health: 1. + 0.03 * turns,
+        speed: 1. + (0.01 * turns).min(1.),
+    };
+
+    // Sorted so the same seed gives each spawner the same waves every run
+    let mut spawners: Vec<_> = spawners.iter_mut().collect();
+    spawners.sort_by_key(|(_, position)| (position.0.x, position.0.y));
+    for (index, (waves, _)) in spawners.iter_mut().enumerate() {
+        let mut rng = WaveRng::new(endless.seed, run.waves, index);
+        // Start with a pause, so the player gets to see it coming
+        let mut wave_turns: Vec<Vec<String>> = vec![Vec::new(); WAVE_PAUSE];
+        let mut left = budget;
+        loop {
+            let group = if pool.is_empty() || rng.chance() < elite_chance {
+                &elites
+            } else {
+                &pool
+            };
+            // Fill up what is left of the budget with cheaper enemies
+            let mut candidates = affordable(group, left);
+            if candidates.is_empty() {
+                candidates = affordable(&pool, left);
+            }
+            let (name, threat) = match rng.pick(&candidates) {
+                Some(enemy) => *enemy,
+                None => break,
+            };
+            left -= threat;
+
+            let name = name.to_owned();
+            match wave_turns.last_mut() {
+                Some(spawns) if !spawns.is_empty() && rng.chance() < stack_chance => {
+                    spawns.push(name);
+                }
+                _ => wave_turns.push(vec![name]),
+            }
+        }
+
+        waves.waves.push(Wave {
+            name: format!("Endless {}", run.waves),
+            turns: wave_turns,
+            pause: WAVE_PAUSE,
+            scaling,
+        });
+    }
+}
+
+/// Make enemies of scaled waves tougher once they spawn
+pub fn apply_enemy_scaling(
+    mut query: Query<
+        (
+            &EnemyScaling,
+            &mut EnemyHealth,
+            &EnemyArmor,
+            &mut EnemySpeed,
+            &Children,
+        ),
+        Added<EnemyScaling>,
+    >,
+    mut bar_query: Query<&mut crate::track_bar::TrackbarSettings>,
+) {
+    for (scaling, mut health, armor, mut speed, children) in query.iter_mut() {
+        health.0 = (f32::from(health.0) * scaling.health).round().min(255.) as u8;
+        speed.0 *= scaling.speed;
+
+        for child in children.iter() {
+            if let Ok(mut settings) = bar_query.get_mut(*child) {
+                settings.total = usize::from(health.0) + usize::from(armor.0);
+            }
+        }
+    }
+}
+
+/// Count the turns survived in endless mode
+pub fn count_survived_turns(
+    endless: Res<EndlessMode>,
+    mut run: ResMut<EndlessRun>,
+    health: Res<PlayerHealth>,
+) {
+    if !endless.enabled || health.0 == 0 {
+        return;
+    }
+
+    run.turns += 1;
+    run.best = run.best.max(run.turns);
+}
+
+/// Save the run as a high score once the player runs out of health
+pub fn record_lost_run(
+    endless: Res<EndlessMode>,
+    run: Res<EndlessRun>,
+    mut scores: ResMut<HighScores>,
+    health: Res<PlayerHealth>,
+) {
+    if endless.enabled && health.is_changed() && health.0 == 0 {
+        scores.record(&run);
+    }
+}
+
+/// Save the run as a high score when the level is left
+pub fn record_left_run(
+    endless: Res<EndlessMode>,
+    run: Res<EndlessRun>,
+    mut scores: ResMut<HighScores>,
+) {
+    if endless.enabled {
+        scores.record(&run);
+    }
+}
+
+/// Save the run as a high score when the game is closed in the middle of it
+///
+/// This runs in the last stage, so the exit event is seen before the app stops
+pub fn record_run_on_exit(
+    mut exits: EventReader<bevy::app::AppExit>,
+    endless: Res<EndlessMode>,
+    run: Res<EndlessRun>,
+    mut scores: ResMut<HighScores>,
+) {
+    if exits.iter().count() > 0 && endless.enabled {
+        scores.record(&run);
+    }
+}
+
+/// Name of the level being played, high scores are kept by it
+fn level_identifier(
+    assets: &crate::assets::MiscAssets,
+    asset_store: &Assets<LdtkAsset>,
+    current_level: &LevelSelection,
+) -> Option<String> {
+    let world_data = asset_store.get(&assets.ldtk_source_file)?;
+    let level_data = world_data.get_level(current_level)?;
+    Some(level_data.identifier.clone())
+}
@@ -2,6 +2,7 @@
 
 use bevy::prelude::*;
 use bevy_prototype_lyon::{entity::ShapeBundle, prelude::*};
+use serde::Deserialize;
 
 /// Marker for a eye entity
 #[derive(Component, Default, Clone, Copy)]
@@ -9,7 +10,7 @@ pub struct EyeMarker;
 
 /// Describes where the eyes are, and how big they are.
 /// Important these distances are based on the orginal img
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone, Copy, Deserialize)]
 pub struct EyeSettings {
     /// Offset from parent center
     pub offset: Vec2,
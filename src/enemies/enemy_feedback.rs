@@ -0,0 +1,85 @@
+//! Show the player what happened to enemies
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_tweening::lens::TransformPositionLens;
+use bevy_tweening::{Animator, EaseFunction, Tween, TweeningType};
+
+use super::enemy_components::EnemyDamaged;
+use crate::animation::{DespawnOnAnimationDone, TextAlphaLens};
+
+/// How long damage numbers stay on screen
+const DAMAGE_NUMBER_DURATION: Duration = Duration::from_millis(700);
+
+/// How far damage numbers float up, in screen pixels
+const DAMAGE_NUMBER_RISE: f32 = 24.;
+
+/// Spawn a number floating up from damaged enemies
+///
+/// These are spawned outside the world so the text is not scaled up with the map and stays crisp
+pub fn spawn_damage_numbers(
+    mut commands: Commands,
+    mut events: EventReader<EnemyDamaged>,
+    // Enemies killed by the damage might have lost their marker already
+    query: Query<&GlobalTransform>,
+    assets: Res<crate::assets::MiscAssets>,
+) {
+    for event in events.iter() {
+        let position = match query.get(event.target) {
+            Ok(position) => position.translation().truncate().extend(50.),
+            Err(_) => continue,
+        };
+
+        spawn_floating_text(
+            &mut commands,
+            &assets,
+            position,
+            event.amount.to_string(),
+            Color::rgb(1., 0.3, 0.3),
+        );
+    }
+}
+
+/// Spawn text that floats up from `position` and fades out, like damage numbers
+///
+/// The text is not a child of the world, so `position` should come from a `GlobalTransform`
+pub fn spawn_floating_text(
+    commands: &mut Commands,
+    assets: &crate::assets::MiscAssets,
+    position: Vec3,
+    text: String,
+    color: Color,
+) {
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::from_section(
+                text,
+                TextStyle {
+                    font: assets.font.clone(),
+                    font_size: 16.,
+                    color,
+                },
+            )
+            .with_alignment(TextAlignment::CENTER),
+            transform: Transform::from_translation(position),
+            ..default()
+        })
+        .insert(Animator::new(Tween::new(
+            EaseFunction::QuadraticOut,
+            TweeningType::Once,
+            DAMAGE_NUMBER_DURATION,
+            TransformPositionLens {
+                start: position,
+                end: position + Vec3::Y * DAMAGE_NUMBER_RISE,
+            },
+        )))
+        .insert(Animator::new(Tween::new(
+            EaseFunction::QuadraticIn,
+            TweeningType::Once,
+            DAMAGE_NUMBER_DURATION,
+            TextAlphaLens { start: 1., end: 0. },
+        )))
+        .insert(DespawnOnAnimationDone)
+        .insert(crate::RemoveOnGameplayExit);
+}
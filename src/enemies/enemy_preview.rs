@@ -0,0 +1,205 @@
+//! Show where enemies will be in the coming turns, so the player can plan around it
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use iyes_loopless::prelude::*;
+
+use super::enemy_components::{
+    EnemyKilled, EnemyMarker, EnemyPath, EnemySpeed, Footprint, Hidden, MoveProgress,
+};
+use super::enemy_status::StatusEffects;
+use super::enemy_types::{EnemyRegistry, EnemyType};
+use crate::grid_position::{GridPosition, TILE_SIZE};
+use crate::ldtk_loader::get_tilemap;
+use crate::{TurnPart, TurnState};
+
+/// Most turns the preview can look ahead
+const MAX_PREVIEW_TURNS: u8 = 3;
+
+/// How many turns ahead should enemy moves be shown? Zero turns the preview off
+#[derive(Debug, Clone, Copy)]
+pub struct MovePreviewTurns(pub u8);
+
+impl Default for MovePreviewTurns {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+/// Marker for the ghosts, trails and leak warnings of the move preview
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct MovePreviewMarker;
+
+/// Cycle how many turns the preview shows when P is pressed
+pub fn toggle_move_preview(keys: Res<Input<KeyCode>>, mut preview: ResMut<MovePreviewTurns>) {
+    if keys.just_pressed(KeyCode::P) {
+        preview.0 = (preview.0 + 1) % (MAX_PREVIEW_TURNS + 1);
+    }
+}
+
+/// Draw where every enemy will be after each of the coming turns during the player turn
+///
+/// Enemies are walked along their path with the same rules as `move_enemies`,
+/// enemies that would reach the end of their path are shown in red on a red tile.
+pub fn update_move_preview(
+    mut commands: Commands,
+    state: Res<CurrentState<TurnState>>,
+    preview: Res<MovePreviewTurns>,
+    mut killed_events: EventReader<EnemyKilled>,
+    changed: Query<
+        (),
+        (
+            With<EnemyMarker>,
+            Or<(Changed<GridPosition>, Changed<StatusEffects>)>,
+        ),
+    >,
+    enemies: Query<
+        (
+            &GridPosition,
+            &EnemyPath,
+            &EnemyType,
+            &StatusEffects,
+            &MoveProgress,
+            &EnemySpeed,
+            &Footprint,
+        ),
+        // The player should not learn where hidden enemies are going
+        (With<EnemyMarker>, Without<Hidden>),
+    >,
+    preview_query: Query<Entity, With<MovePreviewMarker>>,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+    registry: Res<EnemyRegistry>,
+    assets: Res<crate::assets::MiscAssets>,
+    asset_store: Res<Assets<bevy_ecs_ldtk::LdtkAsset>>,
+    current_level: Res<bevy_ecs_ldtk::LevelSelection>,
+) {
+    let killed = killed_events.iter().count() > 0;
+    if !state.is_changed() && !preview.is_changed() && !killed && changed.is_empty() {
+        return;
+    }
+
+    for entity in preview_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let player_turn = matches!(
+        state.0,
+        TurnState::InTurn(
+            TurnPart::PlayerTurnStart | TurnPart::PlayerAction | TurnPart::PlayerAttack
+        )
+    );
+    if !player_turn || preview.0 == 0 {
+        return;
+    }
+
+    let tilemap = get_tilemap(&assets, &asset_store, &current_level);
+    let world = world_query.single();
+
+    for (position, path, enemy_type, effects, progress, speed, footprint) in enemies.iter() {
+        let definition = match registry.get(*enemy_type) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        let mut path = path.clone();
+        let mut tile = position.0;
+        let mut progress = progress.0;
+
+        let mut footprint = *footprint;
+        // Freeze and slow count down every turn, without touching the real effects
+        let mut effects = effects.clone();
+
+        // Big enemies are drawn in the middle of the tiles they cover
+        let center =
+            |tile, footprint: Footprint| GridPosition(tile).to_world() + footprint.center_offset();
+        let mut trail = vec![center(position.0, footprint)];
+        let mut ghosts = Vec::new();
+        let mut leaks = false;
+        for turn in 1..=preview.0 {
+            let budget = progress + speed.0 * effects.speed_multiplier();
+            effects.tick_movement();
+            progress = path.walk(
+                &mut tile,
+                budget,
+                definition.flying(),
+                &mut footprint,
+                tilemap,
+                |_, tile, footprint| {
+                    trail.push(center(tile, footprint));
+                },
+            );
+
+            if ghosts.last().map_or(position.0, |(_, last, _)| *last) != tile {
+                ghosts.push((turn, tile, footprint));
+            }
+            // Leaking enemies are removed at the end of the enemy turn
+            if path.0 == path.1.len() {
+                leaks = true;
+                break;
+            }
+        }
+
+        if ghosts.is_empty() {
+            continue;
+        }
+
+        let color = |alpha| {
+            let mut color = if leaks {
+                Color::rgb(1., 0.3, 0.3)
+            } else {
+                Color::WHITE
+            };
+            color.set_a(alpha);
+            color
+        };
+        commands.entity(world).add_children(|parent| {
+            let mut line = PathBuilder::new();
+            line.move_to(trail[0]);
+            for point in &trail[1..] {
+                line.line_to(*point);
+            }
+            parent
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &line.build(),
+                    DrawMode::Stroke(StrokeMode::new(color(0.4), 1.)),
+                    Transform::from_xyz(0., 0., 8.),
+                ))
+                .insert(MovePreviewMarker)
+                .insert(crate::RemoveOnGameplayExit);
+
+            for (turn, tile, footprint) in &ghosts {
+                parent
+                    .spawn_bundle(SpriteBundle {
+                        texture: definition.texture.clone(),
+                        sprite: Sprite {
+                            // Turns further away are fainter
+                            color: color(0.6 / f32::from(*turn)),
+                            ..default()
+                        },
+                        transform: Transform {
+                            translation: center(*tile, *footprint).extend(9.),
+                            scale: Vec3::new(10. / 16., 10. / 16., 1.)
+                                * Vec3::new(definition.scale, definition.scale, 1.),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .insert(MovePreviewMarker)
+                    .insert(crate::RemoveOnGameplayExit);
+            }
+
+            if leaks {
+                parent
+                    .spawn_bundle(GeometryBuilder::build_as(
+                        &shapes::Rectangle {
+                            extents: Vec2::splat(TILE_SIZE),
+                            origin: RectangleOrigin::Center,
+                        },
+                        DrawMode::Fill(FillMode::color(Color::rgba(1., 0., 0., 0.3))),
+                        Transform::from_translation(GridPosition(tile).to_world().extend(4.)),
+                    ))
+                    .insert(MovePreviewMarker)
+                    .insert(crate::RemoveOnGameplayExit);
+            }
+        });
+    }
+}
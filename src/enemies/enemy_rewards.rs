@@ -0,0 +1,40 @@
+//! What the player gets or loses from enemies
+//!
+//! Killing a enemy pays its bounty, every enemy that gets through costs health
+
+use bevy::prelude::*;
+
+use super::enemy_components::{EnemyKilled, EnemyMarker, EnemyPath};
+use super::enemy_types::{EnemyRegistry, EnemyType};
+
+/// Remove enemies that reached the end of their path, the player loses health for each
+pub fn leak_enemies(
+    mut commands: Commands,
+    query: Query<(Entity, &EnemyPath, &EnemyType), With<EnemyMarker>>,
+    registry: Res<EnemyRegistry>,
+    mut health: ResMut<crate::player::PlayerHealth>,
+) {
+    for (entity, path, enemy_type) in query.iter() {
+        if path.0 == path.1.len() {
+            health.0 = health.0.saturating_sub(
+                registry
+                    .get(*enemy_type)
+                    .map_or(1, |definition| definition.leak_damage),
+            );
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Give the player gold for every enemy killed
+pub fn collect_bounties(
+    mut events: EventReader<EnemyKilled>,
+    registry: Res<EnemyRegistry>,
+    mut gold: ResMut<crate::player::PlayerGold>,
+) {
+    for event in events.iter() {
+        gold.0 += registry
+            .get(event.enemy_type)
+            .map_or(0, |definition| definition.bounty);
+    }
+}
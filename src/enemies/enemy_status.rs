@@ -0,0 +1,346 @@
+//! Status effects that last a number of turns, like poison and freeze
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use super::enemy_components::{DamageEnemy, EnemyMarker};
+use super::enemy_damage::DamageType;
+
+/// The different kinds of status effects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatusKind {
+    /// Take damage at the start of every enemy turn, stacks its damage
+    Poison,
+    /// Move at half speed
+    Slow,
+    /// Skip moving, removed by burn
+    Freeze,
+    /// Take damage at the end of every enemy turn, removed by freeze
+    Burn,
+    /// Take extra damage from every hit
+    Weaken,
+}
+
+impl StatusKind {
+    /// Name shown to the player
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Poison => "Poison",
+            Self::Slow => "Slow",
+            Self::Freeze => "Freeze",
+            Self::Burn => "Burn",
+            Self::Weaken => "Weaken",
+        }
+    }
+
+    /// Color of the icon shown above affected enemies
+    pub fn color(self) -> Color {
+        match self {
+            Self::Poison => Color::rgb(0.3, 0.8, 0.2),
+            Self::Slow => Color::rgb(0.4, 0.6, 1.),
+            Self::Freeze => Color::rgb(0.8, 1., 1.),
+            Self::Burn => Color::rgb(1., 0.5, 0.1),
+            Self::Weaken => Color::rgb(0.6, 0.3, 0.8),
+        }
+    }
+
+    /// Effect that is removed when this one is applied
+    fn cancels(self) -> Option<Self> {
+        match self {
+            Self::Freeze => Some(Self::Burn),
+            Self::Burn => Some(Self::Freeze),
+            Self::Poison | Self::Slow | Self::Weaken => None,
+        }
+    }
+}
+
+/// A status effect on a enemy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusEffect {
+    /// What kind of effect is this?
+    pub kind: StatusKind,
+    /// Damage done by poison and burn, or extra damage taken for weaken
+    pub strength: u8,
+    /// How many more turns does the effect last?
+    pub turns: u8,
+}
+
+impl StatusEffect {
+    /// Short description shown to the player
+    pub fn describe(self) -> String {
+        match self.kind {
+            StatusKind::Poison | StatusKind::Burn | StatusKind::Weaken => format!(
+                "{} {} for {} turns",
+                self.kind.name(),
+                self.strength,
+                self.turns
+            ),
+            StatusKind::Slow | StatusKind::Freeze => {
+                format!("{} for {} turns", self.kind.name(), self.turns)
+            }
+        }
+    }
+}
+
+/// Status effects currently on a enemy, there is at most one of each kind
+#[derive(Component, Debug, Clone, Default)]
+pub struct StatusEffects(pub Vec<StatusEffect>);
+
+impl StatusEffects {
+    /// Get the effect of this kind, if the enemy has it
+    pub fn get(&self, kind: StatusKind) -> Option<StatusEffect> {
+        self.0.iter().find(|effect| effect.kind == kind).copied()
+    }
+
+    /// Add a effect
+    ///
+    /// Poison adds its damage to the existing poison, other effects keep the strongest strength.
+    /// In both cases the longest duration is kept.
+    pub fn add(&mut self, new: StatusEffect) {
+        if let Some(cancelled) = new.kind.cancels() {
+            self.0.retain(|effect| effect.kind != cancelled);
+        }
+
+        match self.0.iter_mut().find(|effect| effect.kind == new.kind) {
+            Some(existing) => {
+                existing.strength = if new.kind == StatusKind::Poison {
+                    existing.strength.saturating_add(new.strength)
+                } else {
+                    existing.strength.max(new.strength)
+                };
+                existing.turns = existing.turns.max(new.turns);
+            }
+            None => self.0.push(new),
+        }
+    }
+
+    /// Count down one turn of this kind of effect, removing it when it runs out
+    ///
+    /// Returns the effect as it was before counting down
+    pub fn tick(&mut self, kind: StatusKind) -> Option<StatusEffect> {
+        let effect = self.get(kind)?;
+        for effect in &mut self.0 {
+            if effect.kind == kind {
+                effect.turns = effect.turns.saturating_sub(1);
+            }
+        }
+        self.0.retain(|effect| effect.turns > 0);
+        Some(effect)
+    }
+
+    /// How much of its speed can the enemy use this turn?
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.get(StatusKind::Freeze).is_some() {
+            0.
+        } else if self.get(StatusKind::Slow).is_some() {
+            0.5
+        } else {
+            1.
+        }
+    }
+
+    /// Count down freeze and slow once the enemy had its move
+    pub fn tick_movement(&mut self) {
+        self.tick(StatusKind::Freeze);
+        self.tick(StatusKind::Slow);
+    }
+}
+
+/// Ask for a status effect to be applied to a enemy
+#[derive(Debug, Clone, Copy)]
+pub struct ApplyStatus {
+    /// Enemy to apply the effect to
+    pub target: Entity,
+    /// The effect to apply
+    pub effect: StatusEffect,
+}
+
+/// Marker for the icons showing the status effects on a enemy
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct StatusIconMarker;
+
+/// Add requested status effects to enemies
+pub fn apply_status_effects(
+    mut events: EventReader<ApplyStatus>,
+    mut query: Query<&mut StatusEffects, With<EnemyMarker>>,
+) {
+    for event in events.iter() {
+        if let Ok(mut effects) = query.get_mut(event.target) {
+            effects.add(event.effect);
+        }
+    }
+}
+
+/// Damage poisoned enemies at the start of the enemy turn
+///
+/// Effects are only borrowed mutably when there is something to count down,
+/// so `Changed<StatusEffects>` only picks up enemies whose effects really changed
+pub fn poison_damage(
+    mut query: Query<(Entity, &mut StatusEffects), With<EnemyMarker>>,
+    mut damage_events: EventWriter<DamageEnemy>,
+) {
+    for (enemy, mut effects) in query.iter_mut() {
+        if let Some(poison) = effects.get(StatusKind::Poison) {
+            effects.tick(StatusKind::Poison);
+            damage_events.send(DamageEnemy {
+                target: enemy,
+                amount: poison.strength,
+                damage_type: DamageType::Magic,
+                source: None,
+            });
+        }
+    }
+}
+
+/// Damage burning enemies and count down weaken at the end of the enemy turn
+pub fn tick_status_effects(
+    mut query: Query<(Entity, &mut StatusEffects), With<EnemyMarker>>,
+    mut damage_events: EventWriter<DamageEnemy>,
+) {
+    for (enemy, mut effects) in query.iter_mut() {
+        if let Some(burn) = effects.get(StatusKind::Burn) {
+            effects.tick(StatusKind::Burn);
+            damage_events.send(DamageEnemy {
+                target: enemy,
+                amount: burn.strength,
+                damage_type: DamageType::Fire,
+                source: None,
+            });
+        }
+        if effects.get(StatusKind::Weaken).is_some() {
+            effects.tick(StatusKind::Weaken);
+        }
+    }
+}
+
+/// Draw a icon for each status effect next to the health bar
+///
+/// The icons are children of the health bar so they move with it when enemies are stacked
+pub fn update_status_icons(
+    mut commands: Commands,
+    query: Query<(&StatusEffects, &Children), (With<EnemyMarker>, Changed<StatusEffects>)>,
+    bar_query: Query<Option<&Children>, With<crate::track_bar::TrackbarProgess>>,
+    icon_query: Query<(), With<StatusIconMarker>>,
+) {
+    for (effects, children) in query.iter() {
+        for child in children.iter() {
+            let bar_children = match bar_query.get(*child) {
+                Ok(bar_children) => bar_children,
+                Err(_) => continue,
+            };
+
+            for icon in bar_children
+                .into_iter()
+                .flat_map(|children| children.iter())
+            {
+                if icon_query.get(*icon).is_ok() {
+                    commands.entity(*icon).despawn_recursive();
+                }
+            }
+
+            commands.entity(*child).add_children(|parent| {
+                for (index, effect) in effects.0.iter().enumerate() {
+                    parent
+                        .spawn_bundle(GeometryBuilder::build_as(
+                            &shapes::Circle {
+                                radius: 1.5,
+                                center: Vec2::ZERO,
+                            },
+                            DrawMode::Fill(FillMode::color(effect.kind.color())),
+                            Transform {
+                                translation: Vec3::new(7.5 + index as f32 * 3.5, -0.5, 2.),
+                                // The health bar is stretched, so undo that
+                                scale: Vec3::new(1., 1. / 3., 1.),
+                                ..default()
+                            },
+                        ))
+                        .insert(StatusIconMarker);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shorthand for a effect
+    fn effect(kind: StatusKind, strength: u8, turns: u8) -> StatusEffect {
+        StatusEffect {
+            kind,
+            strength,
+            turns,
+        }
+    }
+
+    #[test]
+    fn freeze_removes_burn() {
+        let mut effects = StatusEffects::default();
+        effects.add(effect(StatusKind::Burn, 2, 3));
+        effects.add(effect(StatusKind::Freeze, 0, 2));
+        assert_eq!(effects.get(StatusKind::Burn), None);
+        assert!(effects.get(StatusKind::Freeze).is_some());
+    }
+
+    #[test]
+    fn burn_removes_freeze() {
+        let mut effects = StatusEffects::default();
+        effects.add(effect(StatusKind::Freeze, 0, 2));
+        effects.add(effect(StatusKind::Burn, 2, 3));
+        assert_eq!(effects.get(StatusKind::Freeze), None);
+        assert!(effects.get(StatusKind::Burn).is_some());
+    }
+
+    #[test]
+    fn poison_stacks_and_others_keep_the_strongest() {
+        let mut effects = StatusEffects::default();
+        effects.add(effect(StatusKind::Poison, 2, 3));
+        effects.add(effect(StatusKind::Poison, 1, 5));
+        effects.add(effect(StatusKind::Weaken, 3, 4));
+        effects.add(effect(StatusKind::Weaken, 1, 2));
+        assert_eq!(
+            effects.get(StatusKind::Poison),
+            Some(effect(StatusKind::Poison, 3, 5))
+        );
+        assert_eq!(
+            effects.get(StatusKind::Weaken),
+            Some(effect(StatusKind::Weaken, 3, 4))
+        );
+    }
+
+    #[test]
+    fn tick_counts_down_one_kind_and_removes_it_when_done() {
+        let mut effects = StatusEffects::default();
+        effects.add(effect(StatusKind::Slow, 0, 1));
+        effects.add(effect(StatusKind::Poison, 2, 2));
+
+        // The effect is returned as it was before counting down
+        assert_eq!(
+            effects.tick(StatusKind::Slow),
+            Some(effect(StatusKind::Slow, 0, 1))
+        );
+        assert_eq!(effects.get(StatusKind::Slow), None);
+        assert_eq!(
+            effects.get(StatusKind::Poison),
+            Some(effect(StatusKind::Poison, 2, 2))
+        );
+        assert_eq!(effects.tick(StatusKind::Slow), None);
+    }
+
+    #[test]
+    fn tick_movement_counts_down_freeze_and_slow() {
+        let mut effects = StatusEffects::default();
+        effects.add(effect(StatusKind::Freeze, 0, 1));
+        effects.add(effect(StatusKind::Slow, 0, 2));
+        effects.add(effect(StatusKind::Weaken, 1, 1));
+        assert_eq!(effects.speed_multiplier(), 0.);
+
+        effects.tick_movement();
+        assert_eq!(effects.speed_multiplier(), 0.5);
+        assert!(effects.get(StatusKind::Weaken).is_some());
+
+        effects.tick_movement();
+        assert_eq!(effects.speed_multiplier(), 1.);
+    }
+}
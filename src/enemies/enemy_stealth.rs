@@ -0,0 +1,79 @@
+//! Draw stealthy enemies as a faint shimmer until a detection tower spots them
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use super::enemy_components::{EnemyMarker, FlyingShadowMarker, Hidden, Revealed};
+use super::enemy_eyes::EyeMarker;
+use super::enemy_support::AuraMarker;
+
+/// How see through the body of a hidden enemy is at its most visible
+const SHIMMER_ALPHA: f32 = 0.15;
+
+/// Eyes of hidden enemies stay faintly visible, as a hint something is there
+const HIDDEN_EYE_ALPHA: f32 = 0.4;
+
+/// Make the body of hidden enemies pulse in and out of view
+pub fn shimmer_hidden(
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Sprite), (With<EnemyMarker>, With<Hidden>)>,
+) {
+    for (entity, mut sprite) in query.iter_mut() {
+        // Offset every enemy a bit so they dont all pulse together
+        let phase = time.seconds_since_startup() as f32 * 3. + entity.id() as f32;
+        sprite
+            .color
+            .set_a(SHIMMER_ALPHA * (0.5 + 0.5 * phase.sin()));
+    }
+}
+
+/// Show enemies revealed by a spell or other effect right away, instead of at the start of the next player turn
+pub fn reveal_enemies(
+    mut commands: Commands,
+    query: Query<Entity, (With<EnemyMarker>, With<Hidden>, Added<Revealed>)>,
+) {
+    for enemy in query.iter() {
+        commands.entity(enemy).remove::<Hidden>();
+    }
+}
+
+/// Fade out the eyes and hide the health bar and aura of enemies that become hidden, and bring them back once detected
+pub fn update_hidden_visuals(
+    hidden: Query<Entity, (With<EnemyMarker>, Added<Hidden>)>,
+    revealed: RemovedComponents<Hidden>,
+    mut enemies: Query<(&Children, &mut Sprite), With<EnemyMarker>>,
+    mut eyes: Query<&mut DrawMode, With<EyeMarker>>,
+    mut parts: Query<
+        &mut Visibility,
+        Or<(
+            With<crate::track_bar::TrackbarProgess>,
+            With<FlyingShadowMarker>,
+            With<AuraMarker>,
+        )>,
+    >,
+) {
+    let changes = hidden
+        .iter()
+        .map(|enemy| (enemy, true))
+        .chain(revealed.iter().map(|enemy| (enemy, false)));
+    for (enemy, is_hidden) in changes {
+        let (children, mut sprite) = match enemies.get_mut(enemy) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+        // While hidden `shimmer_hidden` takes care of the body
+        if !is_hidden {
+            sprite.color.set_a(1.);
+        }
+
+        let eye_alpha = if is_hidden { HIDDEN_EYE_ALPHA } else { 1. };
+        for child in children.iter() {
+            if let Ok(mut mode) = eyes.get_mut(*child) {
+                *mode = DrawMode::Fill(FillMode::color(Color::rgba(0., 0., 0., eye_alpha)));
+            }
+            if let Ok(mut visibility) = parts.get_mut(*child) {
+                visibility.is_visible = !is_hidden;
+            }
+        }
+    }
+}
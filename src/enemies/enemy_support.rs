@@ -0,0 +1,318 @@
+//! Support enemies that help the enemies around them at the end of the enemy turn
+//!
+//! Healers give back health, shielders give armor that wears off and summoners spawn minions.
+//! Each of them has a aura showing the area they affect, and buffed enemies flash in its color.
+//! Enemies that attack or stun towers get a aura too, so the player can see which towers are in danger.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use bevy_tweening::{Animator, EaseFunction, Tween, TweeningType};
+
+use super::enemy_components::{EnemyArmor, EnemyHealth, EnemyMarker, EnemyPath};
+use super::enemy_systems::spawn_enemy;
+use super::enemy_types::{EnemyAbility, EnemyRegistry, EnemyType};
+use crate::animation::SpriteTintLens;
+use crate::grid_position::{GridPosition, TILE_SIZE};
+use crate::ldtk_loader::{get_tile_type_at, get_tilemap, TileType};
+
+/// How long buffed enemies flash
+const BUFF_FLASH_DURATION: Duration = Duration::from_millis(400);
+
+/// Color of the healer aura, and of enemies being healed
+const HEAL_COLOR: Color = Color::rgb(0.3, 1., 0.3);
+
+/// Color of the shielder aura, and of enemies being shielded
+const SHIELD_COLOR: Color = Color::rgb(0.5, 0.7, 1.);
+
+/// Color of the summoner aura
+const SUMMON_COLOR: Color = Color::rgb(0.7, 0.3, 0.9);
+
+/// Color of the aura of enemies attacking towers
+const ATTACK_COLOR: Color = Color::rgb(1., 0.3, 0.2);
+
+/// Color of the aura of enemies stunning towers
+const STUN_COLOR: Color = Color::rgb(1., 0.9, 0.3);
+
+/// Armor given by a shielder, it is taken away again once it runs out
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Shielded {
+    /// Armor given
+    pub armor: u8,
+    /// Enemy turns left until it runs out
+    pub turns: u8,
+}
+
+/// Turns since a summoner last summoned
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct SummonTimer(pub u8);
+
+/// Marker for the aura drawn around support enemies
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct AuraMarker;
+
+/// Area and color of the aura of a ability, if it has one
+fn aura(ability: &EnemyAbility) -> Option<(u8, Color)> {
+    match ability {
+        EnemyAbility::Healer { radius, .. } => Some((*radius, HEAL_COLOR)),
+        EnemyAbility::Shielder { radius, .. } => Some((*radius, SHIELD_COLOR)),
+        EnemyAbility::Summoner { .. } => Some((1, SUMMON_COLOR)),
+        EnemyAbility::AttackTowers { range, .. } => Some((*range, ATTACK_COLOR)),
+        EnemyAbility::StunTowers { range, .. } => Some((*range, STUN_COLOR)),
+        EnemyAbility::SpawnOnDeath { .. }
+        | EnemyAbility::Flying
+        | EnemyAbility::Stealth
+        | EnemyAbility::Burrow { .. }
+        | EnemyAbility::StealGold { .. }
+        | EnemyAbility::Boss { .. } => None,
+    }
+}
+
+/// Flash a enemy in the color of the buff it got
+fn flash(commands: &mut Commands, enemy: Entity, color: Color) {
+    commands.entity(enemy).insert(Animator::new(Tween::new(
+        EaseFunction::QuadraticOut,
+        TweeningType::Once,
+        BUFF_FLASH_DURATION,
+        SpriteTintLens {
+            start: color,
+            end: Color::WHITE,
+        },
+    )));
+}
+
+/// Draw the aura of new support enemies, and of enemies that go after towers
+pub fn spawn_auras(
+    mut commands: Commands,
+    query: Query<(Entity, &EnemyType), Added<EnemyMarker>>,
+    registry: Res<EnemyRegistry>,
+) {
+    for (enemy, enemy_type) in query.iter() {
+        let definition = match registry.get(*enemy_type) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        // The aura is a child of the enemy, so undo the scale of the enemy
+        let enemy_scale = 10. / 16. * definition.scale;
+
+        commands.entity(enemy).with_children(|parent| {
+            for (radius, color) in definition.abilities.iter().filter_map(aura) {
+                let mut fill = color;
+                fill.set_a(0.12);
+                let mut outline = color;
+                outline.set_a(0.5);
+
+                parent
+                    .spawn_bundle(GeometryBuilder::build_as(
+                        &shapes::Circle {
+                            radius: (f32::from(radius) + 0.5) * TILE_SIZE / enemy_scale,
+                            center: Vec2::ZERO,
+                        },
+                        DrawMode::Outlined {
+                            fill_mode: FillMode::color(fill),
+                            outline_mode: StrokeMode::new(outline, 1. / enemy_scale),
+                        },
+                        Transform::from_xyz(0., 0., -2.),
+                    ))
+                    .insert(AuraMarker);
+            }
+        });
+    }
+}
+
+/// Healers give health back to the other enemies around them, up to their full health
+pub fn heal_neighbours(
+    mut commands: Commands,
+    healers: Query<(Entity, &GridPosition, &EnemyType), With<EnemyMarker>>,
+    mut enemies: Query<(Entity, &GridPosition, &mut EnemyHealth, &EnemyType), With<EnemyMarker>>,
+    registry: Res<EnemyRegistry>,
+) {
+    for (healer, healer_pos, healer_type) in healers.iter() {
+        for ability in registry.abilities(*healer_type) {
+            let (radius, amount) = match ability {
+                EnemyAbility::Healer { radius, amount } => (*radius, *amount),
+                _ => continue,
+            };
+
+            for (enemy, enemy_pos, mut health, enemy_type) in enemies.iter_mut() {
+                let max_health = registry
+                    .get(*enemy_type)
+                    .map_or(0, |definition| definition.health);
+                if enemy == healer
+                    || health.0 == 0
+                    || health.0 >= max_health
+                    || healer_pos.distance(*enemy_pos) > f32::from(radius)
+                {
+                    continue;
+                }
+
+                health.0 = health.0.saturating_add(amount).min(max_health);
+                flash(&mut commands, enemy, HEAL_COLOR);
+            }
+        }
+    }
+}
+
+/// Shielders give armor to the other enemies around them
+///
+/// Shields dont stack, shielding a enemy that is already shielded only makes it last longer
+pub fn shield_neighbours(
+    mut commands: Commands,
+    shielders: Query<(Entity, &GridPosition, &EnemyType), With<EnemyMarker>>,
+    mut enemies: Query<
+        (
+            Entity,
+            &GridPosition,
+            &mut EnemyArmor,
+            Option<&mut Shielded>,
+        ),
+        With<EnemyMarker>,
+    >,
+    registry: Res<EnemyRegistry>,
+) {
+    // Shields given this turn are only inserted once the commands run
+    let mut shielded = HashSet::new();
+
+    for (shielder, shielder_pos, shielder_type) in shielders.iter() {
+        for ability in registry.abilities(*shielder_type) {
+            let (radius, armor_given, turns) = match ability {
+                EnemyAbility::Shielder {
+                    radius,
+                    armor,
+                    turns,
+                } => (*radius, *armor, *turns),
+                _ => continue,
+            };
+
+            for (enemy, enemy_pos, mut armor, shield) in enemies.iter_mut() {
+                if enemy == shielder || shielder_pos.distance(*enemy_pos) > f32::from(radius) {
+                    continue;
+                }
+
+                match shield {
+                    Some(mut shield) => {
+                        // Top the shield up to the armor this shielder gives
+                        let missing = armor_given.saturating_sub(shield.armor);
+                        armor.0 = armor.0.saturating_add(missing);
+                        shield.armor += missing;
+                        shield.turns = shield.turns.max(turns);
+                    }
+                    None if shielded.contains(&enemy) => continue,
+                    None => {
+                        armor.0 = armor.0.saturating_add(armor_given);
+                        commands.entity(enemy).insert(Shielded {
+                            armor: armor_given,
+                            turns,
+                        });
+                        shielded.insert(enemy);
+                    }
+                }
+                flash(&mut commands, enemy, SHIELD_COLOR);
+            }
+        }
+    }
+}
+
+/// Take the shield armor away again from enemies whose shield ran out
+///
+/// Armor that was already broken is not taken away twice
+pub fn expire_shields(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut EnemyArmor, &mut Shielded)>,
+) {
+    for (enemy, mut armor, mut shield) in query.iter_mut() {
+        shield.turns = shield.turns.saturating_sub(1);
+        if shield.turns == 0 {
+            armor.0 -= shield.armor.min(armor.0);
+            commands.entity(enemy).remove::<Shielded>();
+        }
+    }
+}
+
+/// Summoners spawn minions on the path tiles next to them every few turns
+pub fn summon_minions(
+    mut commands: Commands,
+    summoners: Query<
+        (
+            Entity,
+            &GridPosition,
+            &EnemyPath,
+            &EnemyType,
+            Option<&SummonTimer>,
+        ),
+        With<EnemyMarker>,
+    >,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+    registry: Res<EnemyRegistry>,
+    assets: Res<crate::assets::MiscAssets>,
+    asset_store: Res<Assets<bevy_ecs_ldtk::LdtkAsset>>,
+    current_level: Res<bevy_ecs_ldtk::LevelSelection>,
+) {
+    let tilemap = get_tilemap(&assets, &asset_store, &current_level);
+
+    for (summoner, position, path, enemy_type, timer) in summoners.iter() {
+        for ability in registry.abilities(*enemy_type) {
+            let (enemy, count, every) = match ability {
+                EnemyAbility::Summoner {
+                    enemy,
+                    count,
+                    every,
+                } => (enemy, *count, *every),
+                _ => continue,
+            };
+
+            let turns = timer.map_or(0, |timer| timer.0) + 1;
+            if turns < every {
+                commands.entity(summoner).insert(SummonTimer(turns));
+                continue;
+            }
+            commands.entity(summoner).insert(SummonTimer(0));
+
+            let minion = match registry.find(enemy) {
+                Ok(minion) => minion,
+                Err(err) => {
+                    error!("{err}");
+                    continue;
+                }
+            };
+
+            // The tile ahead and the tile behind, as long as they are part of the path
+            let target = path.1.get(path.0).copied().unwrap_or(position.0);
+            let direction = (target - position.0).clamp(IVec2::NEG_ONE, IVec2::ONE);
+            let mut ahead = path.clone();
+            if position.0 + direction == target {
+                ahead.0 += 1;
+            }
+            let mut spots: Vec<_> = [
+                (ahead, position.0 + direction),
+                (path.clone(), position.0 - direction),
+            ]
+            .into_iter()
+            .filter(|(_, tile)| {
+                *tile != position.0 && get_tile_type_at(tilemap, *tile) == TileType::Path
+            })
+            .collect();
+            if spots.is_empty() {
+                spots.push((path.clone(), position.0));
+            }
+
+            commands
+                .entity(world_query.single())
+                .add_children(|parent| {
+                    for (minion_path, tile) in spots.iter().cycle().take(usize::from(count)) {
+                        let tile = GridPosition(*tile);
+                        spawn_enemy(
+                            parent,
+                            &registry,
+                            minion,
+                            tile.to_world().extend(10.),
+                            minion_path.clone(),
+                            tile,
+                        );
+                    }
+                });
+        }
+    }
+}
@@ -4,148 +4,417 @@ use std::time::Duration;
 
 use bevy::prelude::*;
 use bevy_tweening::lens::{TransformPositionLens, TransformScaleLens};
-use bevy_tweening::{Animator, EaseFunction, Tween, TweeningType};
+use bevy_tweening::{Animator, EaseFunction, Sequence, Tween, TweeningType};
 
 use crate::grid_position::GridPosition;
+use crate::ldtk_loader::get_tilemap;
 
+use super::enemy_boss::Boss;
 use super::enemy_components::{
-    EnemyBundle, EnemyHealth, EnemyMarker, EnemyPath, EnemySpawner, EnemyWaves,
+    Burrow, Burrowed, DamageEnemy, EnemyArmor, EnemyBundle, EnemyDamaged, EnemyHealth, EnemyKilled,
+    EnemyMarker, EnemyPath, EnemyScaling, EnemySpawner, EnemySpeed, EnemyStepped, Flying,
+    FlyingShadowMarker, Footprint, Hidden, MoveProgress, Stealth,
 };
+use super::enemy_damage::{calculate_damage, DamageDealt};
 use super::enemy_eyes::EyesBundle;
+use super::enemy_status::StatusEffects;
+use super::enemy_types::{EnemyRegistry, EnemyType};
+use super::enemy_waves::EnemyWaves;
+
+/// How long a burrowing enemy takes to pop up out of the ground
+const SURFACE_DURATION: Duration = Duration::from_millis(350);
 
 /// Spawn enemies when it is time
+///
+/// Enemies spawned on the same turn stack on the spawn tile
 pub fn spawn_enemies(
     mut commands: Commands,
     mut query: Query<(&Transform, &mut EnemyWaves, &EnemyPath, &GridPosition), With<EnemySpawner>>,
     world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
-    assets: Res<crate::assets::EnemyAssets>,
+    registry: Res<EnemyRegistry>,
 ) {
     query.for_each_mut(|(pos, mut waves, path, grid_pos)| {
-        if waves.0 >= waves.1.len() {
-            // We have hit the end of the wave
-            // TODO: win condition or something like that?
+        if waves.is_done() {
+            // We have hit the end of the waves, `check_waves_cleared` takes it from here
             return;
         }
 
-        let current_wave = waves.1[waves.0];
-        if let Some(enemy_type) = current_wave {
-            let health_bar_settings = crate::track_bar::TrackbarSettings {
-                total: enemy_type.enemy_health() as usize,
-                width: 10.,
-                filled_color: Color::rgba(0.8, 0., 0., 0.7),
-                background_color: Color::rgba(0., 0., 0., 0.8),
-            };
-
-            let world = world_query.single();
-
-            commands.entity(world).add_children(|parent| {
-                parent
-                    // Main enemy attributes
-                    .spawn_bundle(EnemyBundle {
-                        _sprite: SpriteBundle {
-                            texture: enemy_type.enemy_asset(&assets),
-                            transform: Transform::from_translation(
-                                pos.translation.truncate().extend(10.),
-                            ),
-                            ..default()
-                        },
-                        health: EnemyHealth(enemy_type.enemy_health()),
-                        path: path.clone(),
-                        grid_location: *grid_pos,
-                        enemy_type,
-                        ..default()
-                    })
-                    // Spawn eyes
-                    .with_children(|parent| {
-                        for settings in enemy_type.eye_settings() {
-                            parent.spawn_bundle(EyesBundle::from_settings(settings));
-                        }
-                    })
-                    // Spawn enemy health sub entities
-                    .with_children(|parent| {
-                        parent
-                            .spawn_bundle(crate::track_bar::TrackbarBundle {
-                                settings: health_bar_settings,
-                                position: SpatialBundle {
-                                    transform: Transform {
-                                        translation: Vec3::new(0., -10., 1.),
-                                        scale: Vec3::new(1., 3., 1.),
-                                        ..default()
-                                    },
-                                    ..default()
-                                },
-                                ..default()
-                            })
-                            .add_children(|parent| {
-                                crate::track_bar::TrackbarBundle::create_children(
-                                    &health_bar_settings,
-                                    parent,
-                                );
-                            });
+        let wave = &waves.waves[waves.wave];
+        if waves.turn == 0 {
+            info!("{} started", wave.name);
+        }
+        let scaling = wave.scaling;
+        // Unknown enemy types are reported when the level loads
+        let enemy_types: Vec<_> = waves
+            .advance()
+            .iter()
+            .filter_map(|name| registry.find(name).ok())
+            .collect();
+        if enemy_types.is_empty() {
+            return;
+        }
+
+        let enemies: Vec<Entity> = commands
+            .entity(world_query.single())
+            .add_children(|parent| {
+                enemy_types
+                    .into_iter()
+                    .filter_map(|enemy_type| {
+                        spawn_enemy(
+                            parent,
+                            &registry,
+                            enemy_type,
+                            pos.translation.truncate().extend(10.),
+                            path.clone(),
+                            *grid_pos,
+                        )
                     })
-                    // Create spawn anumation
-                    .insert(Animator::new(Tween::new(
-                        EaseFunction::BounceOut,
-                        TweeningType::Once,
-                        Duration::from_millis(1000),
-                        TransformScaleLens {
-                            start: Vec3::ZERO,
-
-                            // Scale from 16 px to 10 px?
-                            // 16 * X = 10 => X = 10 / 16
-                            end: Vec3::new(10. / 16., 10. / 16., 1.),
-                        },
-                    )));
+                    .collect()
             });
+        if scaling != EnemyScaling::default() {
+            for enemy in enemies {
+                commands.entity(enemy).insert(scaling);
+            }
         }
-        waves.0 += 1;
     });
 }
 
-/// Move enemies to next location
+/// Spawn a enemy with its eyes and health bar, growing in with the spawn animation
+///
+/// `translation` is the center of `grid_location`, big enemies are moved to the center of the tiles they cover.
+/// Returns `None` without spawning anything if the type was removed from the definitions.
+pub fn spawn_enemy(
+    parent: &mut ChildBuilder,
+    registry: &EnemyRegistry,
+    enemy_type: EnemyType,
+    translation: Vec3,
+    path: EnemyPath,
+    grid_location: GridPosition,
+) -> Option<Entity> {
+    let definition = registry.get(enemy_type)?;
+    let footprint = Footprint::new(definition.size);
+    // Flying enemies head straight for the end of the path
+    let path = match (definition.flying(), path.1.last()) {
+        (true, Some(end)) => EnemyPath(0, vec![*end]),
+        _ => path,
+    };
+    let health_bar_settings = crate::track_bar::TrackbarSettings {
+        total: usize::from(definition.health) + usize::from(definition.armor),
+        width: 10.,
+        filled_color: Color::rgba(0.8, 0., 0., 0.7),
+        secondary_color: Color::rgba(0.7, 0.75, 0.85, 0.9),
+        background_color: Color::rgba(0., 0., 0., 0.8),
+    };
+
+    let enemy = parent
+        // Main enemy attributes
+        .spawn_bundle(EnemyBundle {
+            _sprite: SpriteBundle {
+                texture: definition.texture.clone(),
+                transform: Transform::from_translation(
+                    translation + footprint.center_offset().extend(0.),
+                ),
+                ..default()
+            },
+            health: EnemyHealth(definition.health),
+            armor: EnemyArmor(definition.armor),
+            speed: EnemySpeed(definition.speed),
+            footprint,
+            path,
+            grid_location,
+            enemy_type,
+            ..default()
+        })
+        // Spawn eyes
+        .with_children(|parent| {
+            for settings in &definition.eyes {
+                parent.spawn_bundle(EyesBundle::from_settings(*settings));
+            }
+        })
+        // Create spawn anumation
+        .insert(Animator::new(Tween::new(
+            EaseFunction::BounceOut,
+            TweeningType::Once,
+            Duration::from_millis(1000),
+            TransformScaleLens {
+                start: Vec3::ZERO,
+
+                // Scale from 16 px to 10 px?
+                // 16 * X = 10 => X = 10 / 16
+                end: Vec3::new(10. / 16., 10. / 16., 1.)
+                    * Vec3::new(definition.scale, definition.scale, 1.),
+            },
+        )));
+
+    // Bosses show their health in the bottom ui instead
+    if definition.boss_phases().is_some() {
+        enemy.insert(Boss::default());
+    } else {
+        // Spawn enemy health sub entities
+        enemy.with_children(|parent| {
+            parent
+                .spawn_bundle(crate::track_bar::TrackbarBundle {
+                    settings: health_bar_settings,
+                    position: SpatialBundle {
+                        transform: Transform {
+                            translation: Vec3::new(0., -10., 1.),
+                            scale: Vec3::new(1., 3., 1.),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ..default()
+                })
+                .add_children(|parent| {
+                    crate::track_bar::TrackbarBundle::create_children(&health_bar_settings, parent);
+                });
+        });
+    }
+
+    if definition.flying() {
+        enemy.insert(Flying).with_children(|parent| {
+            parent
+                .spawn_bundle(SpriteBundle {
+                    texture: definition.texture.clone(),
+                    sprite: Sprite {
+                        color: Color::rgba(0., 0., 0., 0.35),
+                        ..default()
+                    },
+                    // Below and behind the enemy, so it looks like it is in the air
+                    transform: Transform::from_xyz(2., -6., -1.),
+                    ..default()
+                })
+                .insert(FlyingShadowMarker);
+        });
+    }
+    // Nothing has had a chance to detect it yet
+    if definition.stealthy() {
+        enemy.insert(Stealth).insert(Hidden);
+    }
+    if let Some((after, tiles)) = definition.burrow() {
+        let burrow = Burrow {
+            before: after,
+            underground: tiles,
+        };
+        enemy.insert(burrow);
+        if burrow.is_underground() {
+            enemy.insert(Burrowed);
+        }
+    }
+
+    Some(enemy.id())
+}
+
+/// Move enemies along their path, as far as their speed allows
+///
+/// Every tile walked over gets its own tween so enemies still follow the corners of their path
 pub fn move_enemies(
     mut commands: Commands,
-    mut query: Query<(Entity, &Transform, &mut EnemyPath, &mut GridPosition), With<EnemyMarker>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &EnemyType,
+            &mut EnemyPath,
+            &mut GridPosition,
+            &mut StatusEffects,
+            &mut MoveProgress,
+            &EnemySpeed,
+            &mut Footprint,
+            Option<&mut Burrow>,
+        ),
+        With<EnemyMarker>,
+    >,
+    registry: Res<EnemyRegistry>,
+    assets: Res<crate::assets::MiscAssets>,
+    asset_store: Res<Assets<bevy_ecs_ldtk::LdtkAsset>>,
+    current_level: Res<bevy_ecs_ldtk::LevelSelection>,
+    mut stepped_events: EventWriter<EnemyStepped>,
+) {
+    let tilemap = get_tilemap(&assets, &asset_store, &current_level);
+
+    for (
+        entity,
+        mut pos,
+        enemy_type,
+        mut path,
+        mut grid_loc,
+        mut effects,
+        mut progress,
+        speed,
+        mut footprint,
+        mut burrow,
+    ) in query.iter_mut()
+    {
+        // Enemies whose type was removed keep walking as plain enemies
+        let (flying, scale) = registry.get(*enemy_type).map_or((false, 1.), |definition| {
+            (definition.flying(), definition.scale)
+        });
+        let budget = progress.0 + speed.0 * effects.speed_multiplier();
+        // Only borrow the effects mutably when there is something to count down
+        if effects.speed_multiplier() < 1. {
+            effects.tick_movement();
+        }
+
+        let mut steps = Vec::new();
+        let mut translation = pos.translation;
+        let mut position = grid_loc.0;
+        let mut fitted = *footprint;
+        let mut covering = *footprint;
+        let was_underground = burrow
+            .as_deref()
+            .map_or(false, |burrow| burrow.is_underground());
+        progress.0 = path.walk(
+            &mut position,
+            budget,
+            flying,
+            &mut fitted,
+            tilemap,
+            |direction, tile, footprint| {
+                // Big enemies are drawn in the middle of the tiles they cover
+                let end = (GridPosition(tile).to_world() + footprint.center_offset())
+                    .extend(translation.z);
+                steps.push(Tween::new(
+                    EaseFunction::ExponentialInOut,
+                    TweeningType::Once,
+                    Duration::from_millis(300),
+                    TransformPositionLens {
+                        start: translation,
+                        end,
+                    },
+                ));
+                translation = end;
+                let previous = covering;
+                covering = footprint;
+
+                // Underground enemies pass below traps without stepping on anything,
+                // the tile they surface on counts as stepped on
+                if let Some(burrow) = burrow.as_deref_mut() {
+                    burrow.step();
+                    if burrow.is_underground() {
+                        return;
+                    }
+                }
+
+                // Big enemies step on every tile they newly cover
+                for covered in footprint.tiles(tile) {
+                    if !previous.covers(tile - direction, covered) {
+                        stepped_events.send(EnemyStepped {
+                            enemy: entity,
+                            position: GridPosition(covered),
+                        });
+                    }
+                }
+            },
+        );
+
+        // Only touch the position when it changed, since systems like traps and boats watch it
+        if position != grid_loc.0 {
+            grid_loc.0 = position;
+        }
+        if fitted != *footprint {
+            *footprint = fitted;
+        }
+
+        let underground = burrow.map_or(false, |burrow| burrow.is_underground());
+        match (was_underground, underground) {
+            (false, true) => {
+                commands.entity(entity).insert(Burrowed);
+            }
+            (true, false) => {
+                // Stay flat while the mound moves, then pop up out of the ground at the end
+                let scale = Vec3::new(10. / 16., 10. / 16., 1.) * Vec3::new(scale, scale, 1.);
+                pos.scale = scale * Vec3::new(1., 0., 1.);
+                steps.push(Tween::new(
+                    EaseFunction::BackOut,
+                    TweeningType::Once,
+                    SURFACE_DURATION,
+                    TransformScaleLens {
+                        start: pos.scale,
+                        end: scale,
+                    },
+                ));
+                commands.entity(entity).remove::<Burrowed>();
+            }
+            _ => {}
+        }
+
+        if !steps.is_empty() {
+            commands
+                .entity(entity)
+                .insert(Animator::new(Sequence::new(steps)));
+        }
+    }
+}
+
+/// Remove armor and health from damaged enemies, and let everyone know when they die
+pub fn apply_damage(
+    mut events: EventReader<DamageEnemy>,
+    mut damaged_events: EventWriter<EnemyDamaged>,
+    mut killed_events: EventWriter<EnemyKilled>,
+    mut query: Query<
+        (
+            &mut EnemyHealth,
+            &mut EnemyArmor,
+            &StatusEffects,
+            &EnemyType,
+            &GridPosition,
+        ),
+        With<EnemyMarker>,
+    >,
+    registry: Res<EnemyRegistry>,
 ) {
-    for (entity, pos, mut path, mut grid_loc) in query.iter_mut() {
-        if path.0 != path.1.len() {
-            let next_target_point = path.1[path.0];
-            let direction =
-                (next_target_point - IVec2::from(*grid_loc)).clamp(IVec2::NEG_ONE, IVec2::ONE);
-
-            let next_point = grid_loc.0 + direction;
-
-            let world_pos_direction = Vec2::new(direction.x as f32 * 16., direction.y as f32 * 16.);
-            let tween = Tween::new(
-                EaseFunction::ExponentialInOut,
-                TweeningType::Once,
-                Duration::from_millis(300),
-                TransformPositionLens {
-                    start: pos.translation,
-                    end: pos.translation + world_pos_direction.extend(0.),
-                },
+    for event in events.iter() {
+        if let Ok((mut health, mut armor, effects, enemy_type, position)) =
+            query.get_mut(event.target)
+        {
+            let dealt = calculate_damage(
+                event.amount,
+                event.damage_type,
+                registry.get(*enemy_type),
+                armor.0,
+                health.0,
+                effects,
             );
+            if dealt == DamageDealt::default() {
+                continue;
+            }
 
-            commands.entity(entity).insert(Animator::new(tween));
-            grid_loc.0 = next_point;
+            armor.0 -= dealt.armor;
+            health.0 -= dealt.health;
+            damaged_events.send(EnemyDamaged {
+                target: event.target,
+                amount: dealt.armor + dealt.health,
+            });
 
-            // Move to next point
-            if grid_loc.0 == next_target_point {
-                path.0 += 1;
+            if dealt.health > 0 && health.0 == 0 {
+                killed_events.send(EnemyKilled {
+                    entity: event.target,
+                    enemy_type: *enemy_type,
+                    position: *position,
+                    killer: event.source,
+                });
             }
         }
     }
 }
 
-/// Set healthbar progress to the current health
+/// Set healthbar progress to the current health, with armor shown after it
 pub fn update_healthbar(
-    query: Query<(&EnemyHealth, &Children), Changed<EnemyHealth>>,
-    mut progress_query: Query<&mut crate::track_bar::TrackbarProgess>,
+    query: Query<
+        (&EnemyHealth, &EnemyArmor, &Children),
+        Or<(Changed<EnemyHealth>, Changed<EnemyArmor>)>,
+    >,
+    mut progress_query: Query<(
+        &mut crate::track_bar::TrackbarProgess,
+        &mut crate::track_bar::TrackbarSecondary,
+    )>,
 ) {
-    for (health, children) in query.iter() {
+    for (health, armor, children) in query.iter() {
         for child in children.iter() {
-            if let Ok(mut progress) = progress_query.get_mut(*child) {
+            if let Ok((mut progress, mut secondary)) = progress_query.get_mut(*child) {
                 progress.0 = health.0 as usize;
+                secondary.0 = armor.0 as usize;
             }
         }
     }
@@ -155,15 +424,32 @@ pub fn update_healthbar(
 // TODO: we are gonna have to wait until I implement that before we can work on stacking
 
 /// Stack health bars below eachother when enemies occupy the same space
+///
+/// This only runs when enemies move or spawn, or when a enemy dies and leaves its tile
 pub fn stack_enemies(
+    mut killed_events: EventReader<EnemyKilled>,
+    moved: Query<(), (With<EnemyMarker>, Changed<GridPosition>)>,
     query: Query<(Entity, &GridPosition, &Children), With<EnemyMarker>>,
     mut bar_query: Query<&mut Transform, With<crate::track_bar::TrackbarProgess>>,
 ) {
+    // Enemies that just died might still have their marker, so leave them out by hand
+    let killed: Vec<Entity> = killed_events.iter().map(|event| event.entity).collect();
+    if killed.is_empty() && moved.is_empty() {
+        return;
+    }
+
     for (entity, position, children) in &query {
+        if killed.contains(&entity) {
+            continue;
+        }
+
         // find enemies in same grid position
         let mut in_same_position: Vec<Entity> = vec![entity];
         for (other_entity, other_position, _) in &query {
-            if other_entity != entity && other_position == position {
+            if other_entity != entity
+                && other_position == position
+                && !killed.contains(&other_entity)
+            {
                 in_same_position.push(other_entity);
             }
         }
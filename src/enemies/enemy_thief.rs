@@ -0,0 +1,87 @@
+//! Enemies that steal gold from the player, killing them gets it back
+
+use bevy::prelude::*;
+
+use super::enemy_components::{EnemyKilled, EnemyMarker, EnemyPath};
+use super::enemy_feedback::spawn_floating_text;
+use super::enemy_types::{EnemyAbility, EnemyRegistry, EnemyType};
+use crate::grid_position::GridPosition;
+use crate::player::PlayerGold;
+
+/// Color of the text showing gold being stolen or given back
+const GOLD_COLOR: Color = Color::rgb(1., 0.85, 0.2);
+
+/// Gold carried by a thief, it is lost for good if the thief leaks
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct StolenGold(pub u32);
+
+/// Thieves close to the end of their path take gold from the player at the end of every enemy turn
+pub fn steal_gold(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &GridPosition,
+            &EnemyPath,
+            &EnemyType,
+            Option<&StolenGold>,
+        ),
+        With<EnemyMarker>,
+    >,
+    registry: Res<EnemyRegistry>,
+    mut gold: ResMut<PlayerGold>,
+    assets: Res<crate::assets::MiscAssets>,
+) {
+    for (enemy, position, grid_pos, path, enemy_type, stolen) in query.iter() {
+        let remaining = path.remaining_distance(grid_pos.0);
+        let amount: u32 = registry
+            .abilities(*enemy_type)
+            .iter()
+            .map(|ability| match ability {
+                EnemyAbility::StealGold { amount, range } if remaining <= i32::from(*range) => {
+                    *amount
+                }
+                _ => 0,
+            })
+            .sum();
+        let taken = amount.min(gold.0);
+        if taken == 0 {
+            continue;
+        }
+
+        gold.0 -= taken;
+        commands
+            .entity(enemy)
+            .insert(StolenGold(stolen.map_or(0, |stolen| stolen.0) + taken));
+        spawn_floating_text(
+            &mut commands,
+            &assets,
+            position.translation().truncate().extend(50.),
+            format!("-{taken} gold"),
+            GOLD_COLOR,
+        );
+    }
+}
+
+/// Give the gold a thief was carrying back to the player when it is killed
+pub fn return_stolen_gold(
+    mut commands: Commands,
+    mut events: EventReader<EnemyKilled>,
+    query: Query<(&StolenGold, &GlobalTransform)>,
+    mut gold: ResMut<PlayerGold>,
+    assets: Res<crate::assets::MiscAssets>,
+) {
+    for event in events.iter() {
+        if let Ok((stolen, position)) = query.get(event.entity) {
+            gold.0 += stolen.0;
+            spawn_floating_text(
+                &mut commands,
+                &assets,
+                position.translation().truncate().extend(50.),
+                format!("+{} gold", stolen.0),
+                GOLD_COLOR,
+            );
+        }
+    }
+}
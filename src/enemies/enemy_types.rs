@@ -1,86 +1,322 @@
 //! Define settings and beheaviour of different enemy types
+//!
+//! The enemy types themself are defined in `Enemies.enemies.ron`, so they can be changed without recompiling
+
+use std::collections::HashMap;
+use std::fmt;
 
-use super::enemy_eyes::EyeSettings;
 use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+
+use super::enemy_boss::{BossPhase, PhaseChange};
+use super::enemy_damage::DamageType;
+use super::enemy_eyes::EyeSettings;
+
+/// Enemy Types, this is the slot of the definition in the [`EnemyRegistry`]
+///
+/// A type keeps its slot when the definitions are reloaded, so existing enemies stay the same type
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EnemyType(pub usize);
 
-/// Enemy Types
-#[derive(Component, Debug, Clone, Copy)]
-pub enum EnemyType {
-    /// Most basic enemy, the slime!
-    Slime,
-    /// Orc
-    Orc,
+/// Special things a enemy can do, enemies without any just walk their path
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum EnemyAbility {
+    /// Spawn other enemies where this one dies, they continue along its path
+    SpawnOnDeath {
+        /// Name of the enemy type to spawn
+        enemy: String,
+        /// How many to spawn
+        count: u8,
+    },
+    /// Fly straight to the end of the path over any terrain, only anti air towers can hit it
+    Flying,
+    /// Can only be seen and attacked while in range of a detection tower
+    Stealth,
+    /// Dig in after walking a few tiles and travel underground, nothing can hit it until it surfaces
+    Burrow {
+        /// Tiles walked above ground before digging in
+        after: u8,
+        /// Tiles walked underground before surfacing again
+        tiles: u8,
+    },
+    /// Heal other enemies around it at the end of every enemy turn
+    Healer {
+        /// How many tiles away enemies are healed
+        radius: u8,
+        /// Health given back each turn
+        amount: u8,
+    },
+    /// Give other enemies around it armor at the end of every enemy turn
+    Shielder {
+        /// How many tiles away enemies are shielded
+        radius: u8,
+        /// Armor given to each enemy
+        armor: u8,
+        /// How many enemy turns the armor lasts
+        turns: u8,
+    },
+    /// Spawn minions on the path next to it every few turns
+    Summoner {
+        /// Name of the enemy type to spawn
+        enemy: String,
+        /// How many to spawn at once
+        count: u8,
+        /// Turns between summons
+        every: u8,
+    },
+    /// Hit the closest tower in range at the end of every enemy turn
+    AttackTowers {
+        /// How many tiles away towers can be hit
+        range: u8,
+        /// Health the tower loses
+        damage: u8,
+    },
+    /// Stun the closest tower in range at the end of every enemy turn, stunned towers dont attack
+    StunTowers {
+        /// How many tiles away towers can be stunned
+        range: u8,
+        /// How many player attacks the tower skips
+        turns: u8,
+    },
+    /// Take gold from the player at the end of every enemy turn once close to the end of the path,
+    /// killing it gets the gold back
+    StealGold {
+        /// Gold taken each turn
+        amount: u32,
+        /// How many tiles from the end of the path it starts stealing
+        range: u8,
+    },
+    /// Shown in the big health bar at the bottom of the screen, and changes as it loses health
+    Boss {
+        /// Phases in the order they happen, each one needs a lower health threshold than the last
+        phases: Vec<BossPhase>,
+    },
 }
 
-// NOTE: should we be using `TryFrom` instead since we can clearly fail?
-// But on the other hand this conversion should never fail in working code, so we might just want to leave the panic in?
-// Since a invalid value will mean there is a error in our program somewhere!
-
-impl From<&str> for EnemyType {
-    fn from(name: &str) -> Self {
-        match name {
-            "Slime" => Self::Slime,
-            "Orc" => Self::Orc,
-            // We panic because the strings based in should always be valid
-            // So a unknown string means there is a type somewhere
-            _ => panic!("unknown enemy variant: {name}"),
-        }
-    }
+/// Everything about a enemy type, as written in the definitions file
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyDefinition {
+    /// Name used by levels and shown to the player
+    pub name: String,
+    /// Path to the sprite, relative to the assets folder
+    pub sprite: String,
+    /// How much health should we have?
+    pub health: u8,
+    /// Armor that has to be broken before health is lost, magic damage ignores it
+    #[serde(default)]
+    pub armor: u8,
+    /// Damage taken from each damage type is multiplied by this, missing types do normal damage
+    #[serde(default)]
+    pub resistances: HashMap<DamageType, f32>,
+    /// how to construct eyes
+    pub eyes: Vec<EyeSettings>,
+    /// How big is the sprite compared to a normal one?
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// How many tiles wide and high is the enemy?
+    #[serde(default = "default_size")]
+    pub size: u8,
+    /// How many tiles does the enemy move each turn?
+    ///
+    /// Below one the enemy only moves every few turns, for example `0.5` moves every other turn
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    /// Gold the player gets for killing it
+    pub bounty: u32,
+    /// Health the player loses when it reaches the end of its path
+    pub leak_damage: u8,
+    /// Special abilities of this enemy
+    #[serde(default)]
+    pub abilities: Vec<EnemyAbility>,
+    /// The loaded sprite, filled in by the [`EnemyRegistry`]
+    #[serde(skip)]
+    pub texture: Handle<Image>,
 }
 
-impl From<String> for EnemyType {
-    fn from(name: String) -> Self {
-        let reference: &str = name.as_ref();
-        reference.into()
+impl EnemyDefinition {
+    /// Does this enemy fly?
+    pub fn flying(&self) -> bool {
+        self.abilities.contains(&EnemyAbility::Flying)
+    }
+
+    /// Is this enemy hidden until detected?
+    pub fn stealthy(&self) -> bool {
+        self.abilities.contains(&EnemyAbility::Stealth)
     }
+
+    /// Tiles walked before digging in and tiles walked underground, if this enemy burrows
+    pub fn burrow(&self) -> Option<(u8, u8)> {
+        self.abilities.iter().find_map(|ability| match ability {
+            EnemyAbility::Burrow { after, tiles } => Some((*after, *tiles)),
+            _ => None,
+        })
+    }
+
+    /// Phases of the boss fight, if this is a boss
+    pub fn boss_phases(&self) -> Option<&[BossPhase]> {
+        self.abilities.iter().find_map(|ability| match ability {
+            EnemyAbility::Boss { phases } => Some(phases.as_slice()),
+            _ => None,
+        })
+    }
+}
+
+/// Enemies move one tile a turn unless the definition says otherwise
+fn default_speed() -> f32 {
+    1.
 }
 
-impl From<&String> for EnemyType {
-    fn from(name: &String) -> Self {
-        let reference: &str = name.as_ref();
-        reference.into()
+/// Enemies are normal sized unless the definition says otherwise
+fn default_scale() -> f32 {
+    1.
+}
+
+/// Enemies cover a single tile unless the definition says otherwise
+fn default_size() -> u8 {
+    1
+}
+
+/// The contents of a enemy definitions file
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "3f6c2f0e-8d4b-4c1e-9a51-7b2d1f0c6e94"]
+pub struct EnemyDefinitions {
+    /// All enemy types
+    pub enemies: Vec<EnemyDefinition>,
+}
+
+/// A level asked for a enemy type that is not defined
+#[derive(Debug, Clone)]
+pub struct UnknownEnemyType {
+    /// The name the level used
+    pub name: String,
+    /// Names that are defined
+    pub known: Vec<String>,
+}
+
+impl fmt::Display for UnknownEnemyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown enemy type `{}`, the known types are: {}",
+            self.name,
+            self.known.join(", ")
+        )
     }
 }
 
-impl EnemyType {
-    /// how to construct eyes
-    pub fn eye_settings(self) -> Vec<EyeSettings> {
-        match self {
-            Self::Slime => vec![
-                EyeSettings {
-                    offset: Vec2::new(-3.0, 3.0),
-                    eye_scale: Vec2::new(4., 4.),
-                    pupil_scale: Vec2::new(2., 2.),
-                },
-                EyeSettings {
-                    offset: Vec2::new(3.0, 3.0),
-                    eye_scale: Vec2::new(4., 4.),
-                    pupil_scale: Vec2::new(2., 2.),
-                },
-            ],
-            Self::Orc => vec![
-                EyeSettings {
-                    offset: Vec2::new(-2., 6.),
-                    eye_scale: Vec2::new(2., 2.),
-                    pupil_scale: Vec2::new(1.5, 1.5)
-                }
-            ]
+impl std::error::Error for UnknownEnemyType {}
+
+/// The loaded enemy types, updated whenever the definitions file changes
+///
+/// Every name gets a slot the first time it is loaded and keeps it, so reordering the file
+/// does not change the type of existing enemies. Removing a definition empties its slot.
+#[derive(Debug, Default)]
+pub struct EnemyRegistry(Vec<(String, Option<EnemyDefinition>)>);
+
+impl EnemyRegistry {
+    /// Load new definitions into their slots, loading the sprites
+    fn update(&mut self, definitions: &EnemyDefinitions, asset_server: &AssetServer) {
+        for (_, slot) in &mut self.0 {
+            *slot = None;
         }
-    }
 
-    /// what img asset should be used for this enemy?
-    pub fn enemy_asset(self, assets: &crate::assets::EnemyAssets) -> Handle<Image> {
-        match self {
-            Self::Slime => assets.slime.clone_weak(),
-            Self::Orc => assets.orc.clone_weak(),
+        for definition in &definitions.enemies {
+            let loaded = EnemyDefinition {
+                texture: asset_server.load(definition.sprite.as_str()),
+                ..definition.clone()
+            };
+            match self.0.iter_mut().find(|(name, _)| *name == definition.name) {
+                Some((_, slot)) => *slot = Some(loaded),
+                None => self.0.push((definition.name.clone(), Some(loaded))),
+            }
         }
     }
 
-    /// How much health should we have?
-    pub fn enemy_health(self) -> u8 {
-        match self {
-            Self::Slime => 2,
-            Self::Orc => 4,
+    /// Get the definition of a enemy type, `None` if it was removed from the definitions file
+    pub fn get(&self, enemy_type: EnemyType) -> Option<&EnemyDefinition> {
+        self.0
+            .get(enemy_type.0)
+            .and_then(|(_, definition)| definition.as_ref())
+    }
+
+    /// Abilities of a enemy type, removed types dont have any
+    pub fn abilities(&self, enemy_type: EnemyType) -> &[EnemyAbility] {
+        self.get(enemy_type)
+            .map_or(&[], |definition| definition.abilities.as_slice())
+    }
+
+    /// Find the enemy type with this name
+    pub fn find(&self, name: &str) -> Result<EnemyType, UnknownEnemyType> {
+        self.types()
+            .find(|enemy_type| self.0[enemy_type.0].0 == name)
+            .ok_or_else(|| UnknownEnemyType {
+                name: name.to_owned(),
+                known: self
+                    .types()
+                    .map(|enemy_type| self.0[enemy_type.0].0.clone())
+                    .collect(),
+            })
+    }
+
+    /// Every enemy type that is currently defined
+    pub fn types(&self) -> impl Iterator<Item = EnemyType> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, definition))| definition.is_some())
+            .map(|(index, _)| EnemyType(index))
+    }
+}
+
+/// Update the registry when the definitions are loaded, or changed on disk
+pub fn update_enemy_registry(
+    mut events: EventReader<AssetEvent<EnemyDefinitions>>,
+    definitions: Res<Assets<EnemyDefinitions>>,
+    asset_server: Res<AssetServer>,
+    mut registry: ResMut<EnemyRegistry>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+
+        if let Some(definitions) = definitions.get(handle) {
+            registry.update(definitions, &asset_server);
+            info!("loaded {} enemy types", registry.types().count());
+
+            // Catch typos now instead of when the enemy dies
+            for ability in registry
+                .types()
+                .flat_map(|enemy_type| registry.abilities(enemy_type))
+            {
+                let spawned = match ability {
+                    EnemyAbility::SpawnOnDeath { enemy, .. }
+                    | EnemyAbility::Summoner { enemy, .. } => vec![enemy],
+                    EnemyAbility::Boss { phases } => phases
+                        .iter()
+                        .flat_map(|phase| &phase.changes)
+                        .filter_map(|change| match change {
+                            PhaseChange::Summon { enemy, .. } => Some(enemy),
+                            PhaseChange::Speed(_) | PhaseChange::Armor(_) => None,
+                        })
+                        .collect(),
+                    EnemyAbility::Flying
+                    | EnemyAbility::Stealth
+                    | EnemyAbility::Burrow { .. }
+                    | EnemyAbility::Healer { .. }
+                    | EnemyAbility::Shielder { .. }
+                    | EnemyAbility::AttackTowers { .. }
+                    | EnemyAbility::StunTowers { .. }
+                    | EnemyAbility::StealGold { .. } => Vec::new(),
+                };
+                for enemy in spawned {
+                    if let Err(err) = registry.find(enemy) {
+                        error!("{err}");
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,375 @@
+//! Show the player which enemies the spawners send in the coming turns
+//!
+//! A panel above the bottom bar lists the next few turns of every spawner,
+//! and a marker above each spawner shows what comes out of it next turn.
+//! The panel also lets the player call the next enemies early for bonus gold.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_tweening::lens::TransformPositionLens;
+use bevy_tweening::{Animator, EaseFunction, Tween, TweeningType};
+use iyes_loopless::prelude::*;
+
+use super::enemy_components::EnemySpawner;
+use super::enemy_types::EnemyRegistry;
+use super::enemy_waves::EnemyWaves;
+use crate::player::PlayerGold;
+use crate::ui::{button_color, BottomBarMarker, BOTTOM_PADDING};
+use crate::{TurnPart, TurnState};
+
+/// How many turns ahead the panel shows
+const PREVIEW_TURNS: usize = 5;
+
+/// Most enemies shown for a single turn, on the panel and above spawners
+const MAX_ICONS: usize = 3;
+
+/// Size of the enemy icons in the panel
+const ICON_SIZE: f32 = 16.;
+
+/// Bonus gold for every turn skipped by calling enemies early
+const CALL_EARLY_GOLD: u32 = 2;
+
+/// Marker for the wave preview panel
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct WavePanelMarker;
+
+/// Marker for the part of the panel listing the spawners, it is rebuilt whenever they move on
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct WaveRowsMarker;
+
+/// Marker for the button calling the next enemies early
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct CallEarlyButton;
+
+/// Marker for the text on the call early button
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct CallEarlyText;
+
+/// Marker for the icons above spawners showing the enemies they spawn next turn
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct SpawnPreviewIcons;
+
+/// Add the hidden wave preview panel to the bottom bar once it exists
+pub fn create_wave_panel(
+    mut commands: Commands,
+    bar_query: Query<Entity, Added<BottomBarMarker>>,
+    assets: Res<crate::assets::MiscAssets>,
+) {
+    for bar in bar_query.iter() {
+        commands.entity(bar).add_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        // Just above the bottom bar, on the left of the screen
+                        position: UiRect {
+                            left: Val::Px(16.),
+                            bottom: Val::Px(BOTTOM_PADDING + 4.),
+                            ..default()
+                        },
+                        flex_direction: FlexDirection::ColumnReverse,
+                        padding: UiRect::all(Val::Px(4.)),
+                        display: Display::None,
+                        ..default()
+                    },
+                    color: Color::rgba(0., 0., 0., 0.6).into(),
+                    ..default()
+                })
+                .insert(Name::new("Wave Panel"))
+                .insert(WavePanelMarker)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::ColumnReverse,
+                                ..default()
+                            },
+                            color: Color::NONE.into(),
+                            ..default()
+                        })
+                        .insert(WaveRowsMarker);
+
+                    parent
+                        .spawn_bundle(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(160.), Val::Px(20.)),
+                                margin: UiRect {
+                                    top: Val::Px(4.),
+                                    ..default()
+                                },
+                                align_items: AlignItems::Center,
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            color: button_color(false, Interaction::None).into(),
+                            ..default()
+                        })
+                        .insert(CallEarlyButton)
+                        .with_children(|parent| {
+                            parent
+                                .spawn_bundle(TextBundle::from_section(
+                                    "",
+                                    TextStyle {
+                                        font: assets.font.clone_weak(),
+                                        font_size: 12.,
+                                        color: Color::WHITE,
+                                    },
+                                ))
+                                .insert(CallEarlyText);
+                        });
+                });
+        });
+    }
+}
+
+/// Rebuild the wave preview panel when a spawner moves on, hiding it once every wave is sent
+pub fn update_wave_panel(
+    mut commands: Commands,
+    changed: Query<(), Changed<EnemyWaves>>,
+    added: Query<(), Added<WaveRowsMarker>>,
+    spawners: Query<(Entity, &EnemyWaves), With<EnemySpawner>>,
+    mut panel_query: Query<&mut Style, With<WavePanelMarker>>,
+    rows_query: Query<(Entity, Option<&Children>), With<WaveRowsMarker>>,
+    registry: Res<EnemyRegistry>,
+    assets: Res<crate::assets::MiscAssets>,
+) {
+    if changed.is_empty() && added.is_empty() && !registry.is_changed() {
+        return;
+    }
+    let (rows, children) = match rows_query.get_single() {
+        Ok(rows) => rows,
+        Err(_) => return,
+    };
+
+    let mut spawners: Vec<_> = spawners
+        .iter()
+        .filter(|(_, waves)| !waves.is_done())
+        .collect();
+    spawners.sort_by_key(|(entity, _)| *entity);
+
+    let display = if spawners.is_empty() {
+        Display::None
+    } else {
+        Display::Flex
+    };
+    for mut style in panel_query.iter_mut() {
+        style.display = display;
+    }
+
+    let text_style = TextStyle {
+        font: assets.font.clone_weak(),
+        font_size: 12.,
+        color: Color::WHITE,
+    };
+    if let Some(children) = children {
+        for child in children.iter() {
+            commands.entity(*child).despawn_recursive();
+        }
+    }
+    commands.entity(rows).add_children(|parent| {
+        parent.spawn_bundle(TextBundle::from_section("Coming up", text_style.clone()));
+
+        for (_, waves) in spawners {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        margin: UiRect {
+                            top: Val::Px(2.),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    let name = waves.next_wave().map_or("", |wave| wave.name.as_str());
+                    parent.spawn_bundle(
+                        TextBundle::from_section(name, text_style.clone()).with_style(Style {
+                            size: Size::new(Val::Px(72.), Val::Auto),
+                            ..default()
+                        }),
+                    );
+
+                    for (turn, spawns) in waves.upcoming(PREVIEW_TURNS).iter().enumerate() {
+                        spawn_turn_preview(parent, turn + 1, spawns, &registry, &text_style);
+                    }
+                });
+        }
+    });
+}
+
+/// A column of the panel with the turn number above the enemies spawned on that turn
+fn spawn_turn_preview(
+    parent: &mut ChildBuilder,
+    turn: usize,
+    spawns: &[String],
+    registry: &EnemyRegistry,
+    text_style: &TextStyle,
+) {
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(ICON_SIZE * MAX_ICONS as f32 + 8.), Val::Auto),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                format!("+{turn}"),
+                text_style.clone(),
+            ));
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Auto, Val::Px(ICON_SIZE)),
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    // Unknown enemy types are reported when the level loads
+                    let icons = spawns
+                        .iter()
+                        .filter_map(|name| registry.get(registry.find(name).ok()?));
+                    for definition in icons.take(MAX_ICONS) {
+                        parent.spawn_bundle(ImageBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(ICON_SIZE), Val::Px(ICON_SIZE)),
+                                ..default()
+                            },
+                            image: UiImage(definition.texture.clone()),
+                            ..default()
+                        });
+                    }
+                });
+        });
+}
+
+/// Show the enemies a spawner sends next turn above it, gently bobbing up and down
+pub fn update_spawn_preview_icons(
+    mut commands: Commands,
+    spawners: Query<(Entity, &EnemyWaves, Option<&Children>), With<EnemySpawner>>,
+    changed: Query<(), (With<EnemySpawner>, Changed<EnemyWaves>)>,
+    icons: Query<(), With<SpawnPreviewIcons>>,
+    registry: Res<EnemyRegistry>,
+) {
+    if changed.is_empty() && !registry.is_changed() {
+        return;
+    }
+
+    for (spawner, waves, children) in spawners.iter() {
+        if let Some(children) = children {
+            for child in children.iter() {
+                if icons.get(*child).is_ok() {
+                    commands.entity(*child).despawn_recursive();
+                }
+            }
+        }
+
+        let next: Vec<_> = waves
+            .upcoming(1)
+            .concat()
+            .iter()
+            .filter_map(|name| registry.get(registry.find(name).ok()?))
+            .map(|definition| definition.texture.clone())
+            .take(MAX_ICONS)
+            .collect();
+        if next.is_empty() {
+            continue;
+        }
+
+        let start = Vec3::new(0., 14., 20.);
+        commands.entity(spawner).with_children(|parent| {
+            parent
+                .spawn_bundle(SpatialBundle::default())
+                .insert(SpawnPreviewIcons)
+                .insert(Animator::new(Tween::new(
+                    EaseFunction::SineInOut,
+                    TweeningType::PingPong,
+                    Duration::from_millis(800),
+                    TransformPositionLens {
+                        start,
+                        end: start + Vec3::Y * 2.,
+                    },
+                )))
+                .with_children(|parent| {
+                    // Side by side, centered over the spawner
+                    let width = 9.;
+                    let offset = (next.len() - 1) as f32 * width / 2.;
+                    for (index, texture) in next.into_iter().enumerate() {
+                        parent.spawn_bundle(SpriteBundle {
+                            texture,
+                            sprite: Sprite {
+                                color: Color::rgba(1., 1., 1., 0.8),
+                                custom_size: Some(Vec2::splat(8.)),
+                                ..default()
+                            },
+                            transform: Transform::from_xyz(index as f32 * width - offset, 0., 0.),
+                            ..default()
+                        });
+                    }
+                });
+        });
+    }
+}
+
+/// Call the next enemies early when the button is clicked or C is pressed, for bonus gold
+///
+/// Every spawner skips the same number of turns so they stay in step,
+/// the preview and the markers update right away since the waves change.
+pub fn call_wave_early(
+    keys: Res<Input<KeyCode>>,
+    state: Res<CurrentState<TurnState>>,
+    mut spawners: Query<&mut EnemyWaves, With<EnemySpawner>>,
+    mut button_query: Query<(&Interaction, &mut UiColor), With<CallEarlyButton>>,
+    clicked_query: Query<&Interaction, (Changed<Interaction>, With<CallEarlyButton>)>,
+    mut text_query: Query<&mut Text, With<CallEarlyText>>,
+    mut gold: ResMut<PlayerGold>,
+) {
+    let player_action = state.0 == TurnState::InTurn(TurnPart::PlayerAction);
+    // Spawners that are done dont hold anything up
+    let skippable = spawners
+        .iter()
+        .filter_map(EnemyWaves::quiet_turns)
+        .min()
+        .unwrap_or(0);
+    let available = player_action && skippable > 0;
+    let bonus = skippable as u32 * CALL_EARLY_GOLD;
+
+    // Only call once per click, not on every frame the button is held down
+    let clicked = keys.just_pressed(KeyCode::C)
+        || clicked_query
+            .iter()
+            .any(|interaction| *interaction == Interaction::Clicked);
+    for (interaction, mut color) in button_query.iter_mut() {
+        color.0 = button_color(available, *interaction);
+    }
+
+    let label = if available {
+        format!("Call early +{bonus} gold (C)")
+    } else {
+        "Call early (C)".to_owned()
+    };
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+
+    if !available || !clicked {
+        return;
+    }
+    for mut waves in spawners.iter_mut() {
+        waves.skip(skippable);
+    }
+    gold.0 += bonus;
+    info!("Called enemies {skippable} turns early for {bonus} gold");
+}
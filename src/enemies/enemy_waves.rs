@@ -0,0 +1,357 @@
+//! Waves of enemies a spawner sends, and the text format levels write them in
+//!
+//! Every line of the `Waves` field of a spawner is a named wave, the name is optional:
+//!
+//! ```text
+//! Scouts: 3x Slime, wait 2, Orc
+//! pause 4
+//! Slime + Slime, 2x Orc + Slime
+//! ```
+//!
+//! - `3x Slime` spawns a slime on each of the next 3 turns
+//! - `Slime + Orc` spawns both on the same turn, stacked on the spawn tile
+//! - `2x Orc + Slime` does that for 2 turns
+//! - `wait 2` spawns nothing for 2 turns
+//! - `pause 4` on its own line waits 4 turns after the wave above it, instead of [`WAVE_PAUSE`]
+//!
+//! Empty lines and lines starting with `#` are skipped.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use super::enemy_components::EnemyScaling;
+use super::enemy_types::EnemyRegistry;
+use crate::utils::find_field;
+
+/// Turns between waves when the level does not say otherwise
+pub const WAVE_PAUSE: usize = 2;
+
+/// A named group of enemies a spawner sends over a few turns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wave {
+    /// Shown to the player
+    pub name: String,
+    /// Names of the enemy types spawned on each turn of the wave
+    pub turns: Vec<Vec<String>>,
+    /// Turns to wait after the wave before the next one starts
+    pub pause: usize,
+    /// How much tougher than their definition the enemies of this wave are
+    pub scaling: EnemyScaling,
+}
+
+/// Spawn order of a spawner, wave by wave
+///
+/// The names are looked up in the `EnemyRegistry` when spawning, since that can change while the level is loaded
+#[derive(Default, Clone, Debug, Component)]
+pub struct EnemyWaves {
+    /// Waves in the order they are sent
+    pub waves: Vec<Wave>,
+    /// Index of the wave being sent, or waiting to be sent
+    pub wave: usize,
+    /// Turns since the current wave started, this runs on into the pause after it
+    pub turn: usize,
+}
+
+impl EnemyWaves {
+    /// Has every wave been sent?
+    pub fn is_done(&self) -> bool {
+        self.wave >= self.waves.len()
+    }
+
+    /// Names of the enemies to spawn this turn, moving on to the next turn
+    pub fn advance(&mut self) -> Vec<String> {
+        let wave = match self.waves.get(self.wave) {
+            Some(wave) => wave,
+            None => return Vec::new(),
+        };
+        let spawns = wave.turns.get(self.turn).cloned().unwrap_or_default();
+
+        // There is nothing to wait for after the last wave
+        let is_last = self.wave + 1 == self.waves.len();
+        let length = wave.turns.len() + if is_last { 0 } else { wave.pause };
+        self.turn += 1;
+        if self.turn >= length {
+            self.wave += 1;
+            self.turn = 0;
+        }
+
+        spawns
+    }
+
+    /// Names of the enemies spawned on each of the next `turns` turns, without moving on
+    pub fn upcoming(&self, turns: usize) -> Vec<Vec<String>> {
+        let mut waves = self.clone();
+        (0..turns).map(|_| waves.advance()).collect()
+    }
+
+    /// Turns until this spawner sends enemies again, `None` once it has nothing left to send
+    pub fn quiet_turns(&self) -> Option<usize> {
+        let mut turns = 0;
+        for (index, wave) in self.waves.iter().enumerate().skip(self.wave) {
+            let start = if index == self.wave { self.turn } else { 0 };
+            let next = wave
+                .turns
+                .iter()
+                .skip(start)
+                .position(|spawns| !spawns.is_empty());
+            if let Some(offset) = next {
+                return Some(turns + offset);
+            }
+
+            // The rest of the wave is empty, so wait out its pause too
+            let is_last = index + 1 == self.waves.len();
+            let length = wave.turns.len() + if is_last { 0 } else { wave.pause };
+            turns += length.saturating_sub(start);
+        }
+        None
+    }
+
+    /// Skip ahead `turns` turns without spawning anything
+    pub fn skip(&mut self, turns: usize) {
+        for _ in 0..turns {
+            self.advance();
+        }
+    }
+
+    /// The wave the next enemies come from, during a pause this is the wave after it
+    pub fn next_wave(&self) -> Option<&Wave> {
+        match self.waves.get(self.wave) {
+            Some(wave) if self.turn < wave.turns.len() => Some(wave),
+            _ => self.waves.get(self.wave + 1),
+        }
+    }
+}
+
+impl From<EntityInstance> for EnemyWaves {
+    fn from(instance: EntityInstance) -> Self {
+        let waves = match find_field(&instance, "Waves") {
+            Some(FieldValue::String(Some(text))) if !text.trim().is_empty() => {
+                match parse_waves(text) {
+                    Ok(waves) => waves,
+                    Err(err) => {
+                        // No waves would clear the level right away, so use the old list instead
+                        error!("{err}, using the `EnemyType` list of the spawner instead");
+                        legacy_waves(&instance)
+                    }
+                }
+            }
+            _ => legacy_waves(&instance),
+        };
+
+        Self { waves, ..default() }
+    }
+}
+
+/// Levels made before the `Waves` field have one optional enemy per turn instead
+fn legacy_waves(instance: &EntityInstance) -> Vec<Wave> {
+    let turns = match find_field(instance, "EnemyType") {
+        Some(FieldValue::Enums(turns)) => turns,
+        _ => {
+            error!("Spawner has no waves and no `EnemyType` list");
+            return Vec::new();
+        }
+    };
+    vec![Wave {
+        name: "Wave 1".to_owned(),
+        turns: turns
+            .iter()
+            .map(|turn| turn.iter().cloned().collect())
+            .collect(),
+        pause: 0,
+        scaling: EnemyScaling::default(),
+    }]
+}
+
+/// Report enemy types the waves of a level use that are not defined
+///
+/// This runs when the level loads, and again for every spawner when the definitions are reloaded
+pub fn check_wave_enemies(
+    added: Query<&EnemyWaves, Added<EnemyWaves>>,
+    spawners: Query<&EnemyWaves>,
+    registry: Res<EnemyRegistry>,
+) {
+    // The definitions are not loaded yet, they check every spawner once they are
+    if registry.types().next().is_none() {
+        return;
+    }
+
+    let waves: Vec<_> = if registry.is_changed() {
+        spawners.iter().collect()
+    } else {
+        added.iter().collect()
+    };
+    // Every name only has to be reported once
+    let names: BTreeSet<&String> = waves
+        .iter()
+        .flat_map(|waves| &waves.waves)
+        .flat_map(|wave| wave.turns.iter().flatten())
+        .collect();
+    for name in names {
+        if let Err(err) = registry.find(name) {
+            error!("{err}");
+        }
+    }
+}
+
+/// A line of a `Waves` field could not be read
+#[derive(Debug, Clone)]
+pub struct WaveParseError {
+    /// Line the problem is on, starting at 1
+    pub line: usize,
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl fmt::Display for WaveParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid wave on line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for WaveParseError {}
+
+/// Read waves written in the format described at the top of this module
+pub fn parse_waves(text: &str) -> Result<Vec<Wave>, WaveParseError> {
+    let mut waves: Vec<Wave> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| WaveParseError {
+            line: index + 1,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(turns) = line.strip_prefix("pause ") {
+            let turns = parse_count(turns).ok_or_else(|| error(format!("bad pause `{line}`")))?;
+            match waves.last_mut() {
+                Some(wave) => wave.pause = turns,
+                None => return Err(error("pause before the first wave".to_owned())),
+            }
+            continue;
+        }
+
+        let (name, entries) = match line.split_once(':') {
+            Some((name, entries)) => (name.trim().to_owned(), entries),
+            None => (format!("Wave {}", waves.len() + 1), line),
+        };
+
+        let mut turns = Vec::new();
+        for entry in entries.split(',').map(str::trim) {
+            if let Some(wait) = entry.strip_prefix("wait ") {
+                let wait = parse_count(wait).ok_or_else(|| error(format!("bad wait `{entry}`")))?;
+                turns.extend(std::iter::repeat(Vec::new()).take(wait));
+                continue;
+            }
+
+            // An optional `3x` in front repeats the group for that many turns
+            let (repeat, group) = match entry.split_once(char::is_whitespace) {
+                Some((count, group)) => match count.strip_suffix('x').and_then(parse_count) {
+                    Some(repeat) => (repeat, group),
+                    None => (1, entry),
+                },
+                None => (1, entry),
+            };
+            let enemies: Vec<String> = group
+                .split('+')
+                .map(|name| name.trim().to_owned())
+                .collect();
+            if enemies.iter().any(String::is_empty) {
+                return Err(error(format!("missing enemy name in `{entry}`")));
+            }
+            turns.extend(std::iter::repeat(enemies).take(repeat));
+        }
+
+        waves.push(Wave {
+            name,
+            turns,
+            pause: WAVE_PAUSE,
+            scaling: EnemyScaling::default(),
+        });
+    }
+
+    Ok(waves)
+}
+
+/// Read a count of turns or enemies
+fn parse_count(text: &str) -> Option<usize> {
+    text.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Turns of enemy names, written out for comparing
+    fn turns(turns: &[&[&str]]) -> Vec<Vec<String>> {
+        turns
+            .iter()
+            .map(|spawns| spawns.iter().map(|&name| name.to_owned()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn counts_repeat_a_group() {
+        let waves = parse_waves("3x Slime, Orc").unwrap();
+        assert_eq!(
+            waves[0].turns,
+            turns(&[&["Slime"], &["Slime"], &["Slime"], &["Orc"]])
+        );
+    }
+
+    #[test]
+    fn plus_spawns_on_the_same_turn() {
+        let waves = parse_waves("Slime + Orc, 2x Orc + Slime").unwrap();
+        assert_eq!(
+            waves[0].turns,
+            turns(&[&["Slime", "Orc"], &["Orc", "Slime"], &["Orc", "Slime"]])
+        );
+    }
+
+    #[test]
+    fn wait_spawns_nothing() {
+        let waves = parse_waves("Slime, wait 2, Orc").unwrap();
+        assert_eq!(waves[0].turns, turns(&[&["Slime"], &[], &[], &["Orc"]]));
+    }
+
+    #[test]
+    fn pause_sets_the_pause_of_the_wave_above() {
+        let waves = parse_waves("Slime\npause 4\nOrc").unwrap();
+        assert_eq!(waves.len(), 2);
+        assert_eq!(waves[0].pause, 4);
+        assert_eq!(waves[1].pause, WAVE_PAUSE);
+    }
+
+    #[test]
+    fn waves_are_named() {
+        let waves = parse_waves("Scouts: 2x Slime\n\n# comment\nOrc").unwrap();
+        assert_eq!(waves.len(), 2);
+        assert_eq!(waves[0].name, "Scouts");
+        assert_eq!(waves[0].turns, turns(&[&["Slime"], &["Slime"]]));
+        assert_eq!(waves[1].name, "Wave 2");
+    }
+
+    #[test]
+    fn pause_before_the_first_wave_is_an_error() {
+        let err = parse_waves("# comment\npause 2\nSlime").unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+
+    #[test]
+    fn bad_counts_are_errors() {
+        assert_eq!(parse_waves("Slime\npause a while").unwrap_err().line, 2);
+        assert_eq!(parse_waves("Slime, wait -1").unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn missing_names_are_errors() {
+        assert!(parse_waves("Slime + , Orc").is_err());
+        assert!(parse_waves("Slime,, Orc").is_err());
+        assert!(parse_waves("Boss:").is_err());
+    }
+}
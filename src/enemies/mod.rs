@@ -1,35 +1,67 @@
 //! Enemy logic
 
+mod enemy_boss;
+mod enemy_burrow;
 mod enemy_components;
+mod enemy_damage;
+mod enemy_death;
+mod enemy_endless;
 mod enemy_eyes;
+mod enemy_feedback;
+mod enemy_preview;
+mod enemy_rewards;
+mod enemy_status;
+mod enemy_stealth;
+mod enemy_support;
+mod enemy_thief;
 mod enemy_systems;
 mod enemy_types;
+mod enemy_wave_preview;
+mod enemy_waves;
 mod enemy_boat;
 
-pub use enemy_components::EnemySpawnerBundle;
+pub use enemy_components::{
+    Burrowed, DamageEnemy, EnemyHealth, EnemyKilled, EnemyMarker, EnemyPath, EnemySpawnerBundle,
+    EnemyStepped, Flying, Footprint, Hidden, Revealed, Stealth,
+};
+pub use enemy_damage::DamageType;
+pub use enemy_death::WavesCleared;
+pub use enemy_endless::{EndlessMode, EndlessRun};
+pub use enemy_preview::MovePreviewTurns;
+pub use enemy_status::{ApplyStatus, StatusEffect, StatusKind};
+pub use enemy_types::{EnemyAbility, EnemyDefinitions, EnemyRegistry, EnemyType};
 
 use crate::{TurnPart, TurnState};
 
 use bevy::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
 use iyes_loopless::prelude::*;
-use iyes_progress::prelude::*;
 
 /// Enemy plugin
 pub struct EnemyPlugin;
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.register_type::<enemy_components::EnemyPath>();
+        app.add_plugin(RonAssetPlugin::<enemy_types::EnemyDefinitions>::new(&["enemies.ron"]));
+        app.init_resource::<enemy_types::EnemyRegistry>();
+        app.add_system(enemy_types::update_enemy_registry);
+        app.add_system(enemy_waves::check_wave_enemies.run_in_state(crate::MainState::Playing));
+        app.add_event::<enemy_components::DamageEnemy>();
+        app.add_event::<enemy_components::EnemyDamaged>();
+        app.add_event::<enemy_components::EnemyKilled>();
+        app.add_event::<enemy_components::EnemyStepped>();
+        app.add_event::<enemy_status::ApplyStatus>();
+
+        app.init_resource::<enemy_death::WavesCleared>();
+        app.init_resource::<enemy_preview::MovePreviewTurns>();
+        app.add_system(enemy_preview::toggle_move_preview.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_preview::update_move_preview.run_in_state(crate::MainState::Playing));
 
         app.add_system(enemy_eyes::move_eyes_to_cursor.run_in_state(crate::MainState::Playing));
 
         app.add_system(crate::utils::give_entity_name::<
             enemy_components::EnemySpawner,
         >("EnemySpanwer".to_owned()));
-        app.add_system(
-            crate::utils::is_animation_done::<Transform>
-                .track_progress()
-                .run_in_state(crate::MainState::Playing),
-        );
 
         // TURN SYSTEMS
         app.add_enter_system(
@@ -40,9 +72,108 @@ impl Plugin for EnemyPlugin {
             TurnState::InTurn(TurnPart::EnemyMove),
             enemy_systems::move_enemies,
         );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::EnemyTurnStart),
+            enemy_status::poison_damage,
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::EnemyTurnEnd),
+            enemy_status::tick_status_effects,
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::EnemyTurnEnd),
+            enemy_rewards::leak_enemies,
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::EnemyTurnEnd),
+            enemy_support::heal_neighbours,
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::EnemyTurnEnd),
+            enemy_support::shield_neighbours,
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::EnemyTurnEnd),
+            enemy_support::summon_minions,
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::EnemyTurnStart),
+            enemy_support::expire_shields,
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::EnemyTurnEnd),
+            enemy_thief::steal_gold,
+        );
+        app.add_system(enemy_thief::return_stolen_gold.run_in_state(crate::MainState::Playing));
 
+        app.add_system(enemy_systems::apply_damage.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_rewards::collect_bounties.run_in_state(crate::MainState::Playing));
+        app.add_system(
+            enemy_feedback::spawn_damage_numbers.run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(enemy_death::start_death_animation.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_death::spawn_on_death.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_death::check_waves_cleared.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_status::apply_status_effects.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_status::update_status_icons.run_in_state(crate::MainState::Playing));
         app.add_system(enemy_systems::update_healthbar.run_in_state(crate::MainState::Playing));
         app.add_system(enemy_systems::stack_enemies.run_in_state(crate::MainState::Playing));
         app.add_system(enemy_boat::spawn_despawn_boats.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_stealth::shimmer_hidden.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_stealth::reveal_enemies.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_support::spawn_auras.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_boss::advance_boss_phases.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_boss::create_boss_bar.run_in_state(crate::MainState::Playing));
+        app.add_system(enemy_boss::update_boss_bar.run_in_state(crate::MainState::Playing));
+        app.add_system(
+            enemy_stealth::update_hidden_visuals.run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(
+            enemy_burrow::update_burrowed_visuals.run_in_state(crate::MainState::Playing),
+        );
+
+        // Wave preview
+        app.add_system(
+            enemy_wave_preview::create_wave_panel.run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(
+            enemy_wave_preview::update_wave_panel.run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(
+            enemy_wave_preview::update_spawn_preview_icons.run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(
+            enemy_wave_preview::call_wave_early.run_in_state(crate::MainState::Playing),
+        );
+
+        // Endless mode
+        app.init_resource::<enemy_endless::EndlessMode>();
+        app.init_resource::<enemy_endless::EndlessRun>();
+        app.insert_resource(enemy_endless::HighScores::load());
+        app.add_enter_system(crate::MainState::Playing, enemy_endless::reset_endless_run);
+        app.add_exit_system(crate::MainState::Playing, enemy_endless::record_left_run);
+        app.add_system(
+            enemy_endless::toggle_endless_mode.run_in_state(crate::MainState::Playing),
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::PlayerTurnEnd),
+            enemy_endless::lock_endless_mode,
+        );
+        app.add_system(enemy_endless::record_lost_run.run_in_state(crate::MainState::Playing));
+        app.add_system_to_stage(
+            CoreStage::Last,
+            enemy_endless::record_run_on_exit.run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(
+            enemy_endless::apply_enemy_scaling.run_in_state(crate::MainState::Playing),
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::PlayerTurnStart),
+            enemy_endless::count_survived_turns.before(enemy_endless::generate_endless_waves),
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::PlayerTurnStart),
+            enemy_endless::generate_endless_waves,
+        );
     }
 }
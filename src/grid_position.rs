@@ -1,13 +1,38 @@
 //! Version of `GridCoords` that uses a `IVec2` instead
 
-use bevy::prelude::{Component, IVec2};
+use bevy::prelude::{Component, IVec2, Vec2};
 use bevy_ecs_ldtk::GridCoords;
 use derive_more::{Add, AddAssign, From, Sub, SubAssign};
 
+/// Size of a single tile in world space (before the world is scaled to fit the window)
+pub const TILE_SIZE: f32 = 16.;
+
 /// Custom gird position tracker
 #[derive(Debug, Copy, Clone, Default, Component, Add, Sub, From, AddAssign, SubAssign, PartialEq, Eq)]
 pub struct GridPosition(pub IVec2);
 
+impl GridPosition {
+    /// Center of this tile relative to the world entity
+    pub fn to_world(self) -> Vec2 {
+        self.0.as_vec2() * TILE_SIZE + Vec2::splat(TILE_SIZE / 2.)
+    }
+
+    /// Tile containing this position, the position should be relative to the world entity
+    pub fn from_world(position: Vec2) -> Self {
+        Self((position / TILE_SIZE).floor().as_ivec2())
+    }
+
+    /// Distance between the centers of two tiles, in tiles
+    pub fn distance(self, other: Self) -> f32 {
+        self.0.as_vec2().distance(other.0.as_vec2())
+    }
+
+    /// Number of tiles between two tiles, counting diagonal neighbours as one tile away
+    pub fn tiles_between(self, other: Self) -> u32 {
+        (self.0 - other.0).abs().max_element() as u32
+    }
+}
+
 impl From<GridCoords> for GridPosition {
     fn from(source: GridCoords) -> Self {
         Self(source.into())
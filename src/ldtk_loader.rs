@@ -44,8 +44,49 @@ pub enum TileType {
     Water,
 }
 
+impl TileType {
+    /// Name shown to the player
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "Nothing",
+            Self::Grass => "Grass",
+            Self::Path => "Path",
+            Self::Water => "Water",
+        }
+    }
+
+    /// How much of a enemies speed does it cost to walk onto this tile?
+    pub fn move_cost(self) -> f32 {
+        match self {
+            Self::Water => 2.,
+            Self::None | Self::Grass | Self::Path => 1.,
+        }
+    }
+}
+
+/// Get the int grid layer of the current level
+pub fn get_tilemap<'a>(
+    assets: &crate::assets::MiscAssets,
+    asset_store: &'a Assets<LdtkAsset>,
+    current_level: &LevelSelection,
+) -> &'a LayerInstance {
+    let world_data = asset_store.get(&assets.ldtk_source_file).unwrap();
+    let level_data = world_data.get_level(current_level).unwrap();
+    level_data.layer_instances.as_ref().unwrap().last().unwrap()
+}
+
 /// Get the int grid tile type from the tileset
+///
+/// Positions outside the map are [`TileType::None`]
 pub fn get_tile_type_at(tilemap: &LayerInstance, position: IVec2) -> TileType {
+    if position.x < 0
+        || position.y < 0
+        || position.x >= tilemap.c_wid
+        || position.y >= tilemap.c_hei
+    {
+        return TileType::None;
+    }
+
     let x = position.x;
     let y = tilemap.c_hei - position.y - 1;
     let index = x + y * tilemap.c_wid;
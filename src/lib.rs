@@ -32,6 +32,8 @@ mod utils;
 // mod transform_stacking;
 mod grid_position;
 
+mod animation;
+
 mod assets;
 mod state;
 mod turns;
@@ -44,6 +46,7 @@ mod ldtk_loader;
 
 mod enemies;
 mod player;
+mod towers;
 
 mod track_bar;
 
@@ -112,6 +115,7 @@ impl Plugin for GamePlugin {
 
         app.add_plugin(mouse_location::MouseWorldPlugin);
         app.add_plugin(camera::CameraPlugin);
+        app.add_plugin(animation::AnimationPlugin);
 
         // Ui Widgets
         app.add_plugin(ui::UiPlugin);
@@ -120,6 +124,7 @@ impl Plugin for GamePlugin {
         // Gameplay plugins
         app.add_plugin(enemies::EnemyPlugin);
         app.add_plugin(player::PlayerPlugin);
+        app.add_plugin(towers::TowerPlugin);
 
         #[cfg(feature = "debug_editor")]
         {
@@ -7,16 +7,24 @@
 use bevy::{prelude::*, render::camera::RenderTarget};
 use iyes_loopless::prelude::*;
 
+use crate::grid_position::GridPosition;
+use crate::ui::BOTTOM_PADDING;
+
 /// Store the mouses position in the world
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct MouseWorldPos(pub Vec2);
 
+/// The tile the mouse is hovering over, `None` when the mouse is over the ui or outside the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MouseGridPos(pub Option<GridPosition>);
+
 /// Keeps track of the mouses world position using a [`MouseWorldPos`] global resource
 pub struct MouseWorldPlugin;
 
 impl Plugin for MouseWorldPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(MouseWorldPos(Vec2::ZERO));
+        app.insert_resource(MouseGridPos::default());
         app.add_system(update_world_position.run_in_state(crate::MainState::Playing));
     }
 }
@@ -24,8 +32,10 @@ impl Plugin for MouseWorldPlugin {
 /// Update mouse world position
 fn update_world_position(
     mut mouse_world_pos: ResMut<MouseWorldPos>,
+    mut mouse_grid_pos: ResMut<MouseGridPos>,
     windows: Res<Windows>,
     q_camera: Query<(&Camera, &GlobalTransform), With<crate::MainCamera>>,
+    q_world: Query<&GlobalTransform, With<crate::ldtk_loader::WorldMarker>>,
 ) {
     let (camera, camera_transform) = q_camera.single();
 
@@ -45,5 +55,22 @@ fn update_world_position(
         let world_pos = world_pos.truncate();
 
         mouse_world_pos.0 = world_pos;
+
+        // The bottom of the screen is covered by the ui, so we are not hovering any tiles there
+        let over_ui = screen_pos.y < BOTTOM_PADDING;
+        mouse_grid_pos.0 = match q_world.get_single() {
+            Ok(world_transform) if !over_ui => {
+                // Tiles live inside the (scaled) world entity, so go into its local space
+                let local_pos = world_transform
+                    .compute_matrix()
+                    .inverse()
+                    .transform_point3(world_pos.extend(0.0))
+                    .truncate();
+                Some(GridPosition::from_world(local_pos))
+            }
+            _ => None,
+        };
+    } else {
+        mouse_grid_pos.0 = None;
     }
 }
@@ -1,7 +1,16 @@
 //! Player control systems
 
 use bevy::prelude::*;
-use iyes_loopless::prelude::AppLooplessStateExt;
+use iyes_loopless::prelude::*;
+use iyes_progress::prelude::*;
+
+use crate::{TurnPart, TurnState};
+
+/// Gold the player starts a level with
+const STARTING_GOLD: u32 = 10;
+
+/// Gold the player gets at the start of each of their turns
+const GOLD_PER_TURN: u32 = 2;
 
 /// Player stuff
 pub struct PlayerPlugin;
@@ -9,13 +18,102 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_enter_system(crate::MainState::Playing, reset_health);
+        app.add_enter_system(crate::MainState::Playing, reset_gold);
+        app.add_enter_system(crate::MainState::Playing, reset_end_turn);
+
+        app.add_enter_system(TurnState::InTurn(TurnPart::PlayerTurnStart), gain_income);
+        app.add_enter_system(TurnState::InTurn(TurnPart::PlayerAction), reset_end_turn);
+        app.add_enter_system(TurnState::InTurn(TurnPart::PlayerAttack), reset_end_turn);
+
+        app.insert_resource(AttackMode::default());
+        app.add_system(toggle_attack_mode.run_in_state(crate::MainState::Playing));
+
+        app.add_system(end_turn_hotkey.run_in_state(TurnState::InTurn(TurnPart::PlayerAction)));
+        app.add_system(end_turn_hotkey.run_in_state(TurnState::InTurn(TurnPart::PlayerAttack)));
+        app.add_system(
+            wait_for_end_turn
+                .track_progress()
+                .run_in_state(TurnState::InTurn(TurnPart::PlayerAction)),
+        );
     }
 }
 
 /// Health the player got left
 pub struct PlayerHealth(pub u8);
 
+/// Gold the player can spend on towers
+#[derive(Debug, Clone, Copy)]
+pub struct PlayerGold(pub u32);
+
+/// Has the player said they are done with their actions this turn?
+///
+/// In manual attack mode this is also used to fire the queued attacks
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EndTurnRequested(pub bool);
+
+/// How do towers pick what to attack?
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AttackMode {
+    /// Towers attack on their own using their targeting mode
+    #[default]
+    Automatic,
+    /// The player orders each attack during the player attack part of the turn
+    Manual,
+}
+
+impl AttackMode {
+    /// Name shown to the player
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Automatic => "Auto attack",
+            Self::Manual => "Manual attack",
+        }
+    }
+
+    /// The other mode
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Automatic => Self::Manual,
+            Self::Manual => Self::Automatic,
+        }
+    }
+}
+
 /// Reset health back to default when we enter gameplay
 fn reset_health(mut commands: Commands) {
     commands.insert_resource(PlayerHealth(10));
-}
\ No newline at end of file
+}
+
+/// Reset gold back to default when we enter gameplay
+fn reset_gold(mut commands: Commands) {
+    commands.insert_resource(PlayerGold(STARTING_GOLD));
+}
+
+/// Make the player confirm the end of their turn again
+fn reset_end_turn(mut commands: Commands) {
+    commands.insert_resource(EndTurnRequested(false));
+}
+
+/// Give the player their gold for this turn
+fn gain_income(mut gold: ResMut<PlayerGold>) {
+    gold.0 += GOLD_PER_TURN;
+}
+
+/// Switch between automatic and manual attacks when M is pressed
+fn toggle_attack_mode(keys: Res<Input<KeyCode>>, mut attack_mode: ResMut<AttackMode>) {
+    if keys.just_pressed(KeyCode::M) {
+        *attack_mode = attack_mode.toggled();
+    }
+}
+
+/// End the turn when space is pressed
+fn end_turn_hotkey(keys: Res<Input<KeyCode>>, mut end_turn: ResMut<EndTurnRequested>) {
+    if keys.just_pressed(KeyCode::Space) {
+        end_turn.0 = true;
+    }
+}
+
+/// Keep the player action part going until the player ends their turn
+fn wait_for_end_turn(end_turn: Res<EndTurnRequested>) -> Progress {
+    end_turn.0.into()
+}
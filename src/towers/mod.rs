@@ -0,0 +1,126 @@
+//! Tower logic
+
+mod tower_attacks;
+mod tower_components;
+mod tower_detection;
+mod tower_health;
+mod tower_panel;
+mod tower_placement;
+mod tower_shop;
+mod tower_systems;
+mod tower_targeting;
+mod tower_traps;
+mod tower_types;
+
+use crate::{TurnPart, TurnState};
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use iyes_progress::prelude::*;
+
+/// Tower plugin
+pub struct TowerPlugin;
+impl Plugin for TowerPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(tower_placement::PlacementMode::default());
+        app.insert_resource(tower_panel::SelectedTower::default());
+        app.insert_resource(tower_panel::TowerConfig::default());
+        app.insert_resource(tower_attacks::AttackQueue::default());
+
+        // Shop
+        app.add_system(tower_shop::create_shop.run_in_state(crate::MainState::Playing));
+        app.add_system(tower_shop::shop_button_clicked.run_in_state(crate::MainState::Playing));
+        app.add_system(tower_shop::update_shop_buttons.run_in_state(crate::MainState::Playing));
+        app.add_system(tower_shop::show_shop_tooltips.run_in_state(crate::MainState::Playing));
+
+        // Placement
+        app.add_system(
+            tower_placement::placement_hotkeys.run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(
+            tower_placement::spawn_ghost_tower.run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(
+            tower_placement::move_ghost_tower.run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(
+            tower_placement::place_tower.run_in_state(TurnState::InTurn(TurnPart::PlayerAction)),
+        );
+        app.add_exit_system(
+            TurnState::InTurn(TurnPart::PlayerAction),
+            tower_placement::cancel_placement,
+        );
+
+        // Selected tower panel
+        app.add_system(tower_panel::create_tower_panel.run_in_state(crate::MainState::Playing));
+        app.add_system(tower_panel::select_tower.run_in_state(crate::MainState::Playing));
+        app.add_system(
+            tower_panel::clear_removed_selection.run_in_state(crate::MainState::Playing),
+        );
+        app.add_system(tower_panel::show_selection_range.run_in_state(crate::MainState::Playing));
+        app.add_system(tower_panel::update_tower_panel.run_in_state(crate::MainState::Playing));
+        app.add_system(
+            tower_panel::upgrade_tower.run_in_state(TurnState::InTurn(TurnPart::PlayerAction)),
+        );
+        app.add_system(tower_panel::cycle_targeting.run_in_state(crate::MainState::Playing));
+        app.add_system(
+            tower_panel::sell_tower.run_in_state(TurnState::InTurn(TurnPart::PlayerAction)),
+        );
+
+        // Tower health
+        app.add_system(tower_health::update_tower_healthbar.run_in_state(crate::MainState::Playing));
+        app.add_system(tower_health::destroy_towers.run_in_state(crate::MainState::Playing));
+        app.add_system(tower_health::show_stun_icons.run_in_state(crate::MainState::Playing));
+
+        // Traps
+        app.add_system(tower_traps::trigger_traps.run_in_state(crate::MainState::Playing));
+        app.add_system(
+            tower_traps::rearm_trap.run_in_state(TurnState::InTurn(TurnPart::PlayerAction)),
+        );
+
+        // TURN SYSTEMS
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::EnemyTurnEnd),
+            tower_health::enemies_attack_towers,
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::PlayerTurnStart),
+            tower_systems::reduce_cooldowns,
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::PlayerTurnStart),
+            tower_detection::detect_stealthy_enemies,
+        );
+        app.add_enter_system(
+            TurnState::InTurn(TurnPart::PlayerAttack),
+            tower_targeting::choose_targets,
+        );
+        app.add_exit_system(
+            TurnState::InTurn(TurnPart::PlayerAttack),
+            tower_attacks::clear_attack_queue,
+        );
+        app.add_exit_system(
+            TurnState::InTurn(TurnPart::PlayerAttack),
+            tower_health::recover_from_stun,
+        );
+        app.add_system(
+            tower_attacks::queue_manual_attack
+                .run_in_state(TurnState::InTurn(TurnPart::PlayerAttack)),
+        );
+        app.add_system(
+            tower_attacks::cancel_queued_attacks
+                .run_in_state(TurnState::InTurn(TurnPart::PlayerAttack)),
+        );
+        app.add_system(
+            tower_attacks::resolve_attacks
+                .track_progress()
+                .run_in_state(TurnState::InTurn(TurnPart::PlayerAttack)),
+        );
+
+        // Attack visuals
+        app.add_system(tower_attacks::show_queued_targets.run_in_state(crate::MainState::Playing));
+        app.add_system(tower_attacks::show_valid_targets.run_in_state(crate::MainState::Playing));
+        app.add_system(tower_attacks::show_blast_preview.run_in_state(crate::MainState::Playing));
+        app.add_system(tower_attacks::projectile_hit.run_in_state(crate::MainState::Playing));
+    }
+}
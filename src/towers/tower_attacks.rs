@@ -0,0 +1,570 @@
+//! Queue up tower attacks and resolve them with projectiles
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use bevy_tweening::lens::{TransformPositionLens, TransformScaleLens};
+use bevy_tweening::{Animator, Delay, EaseFunction, Sequence, Tween, TweeningType};
+use iyes_loopless::prelude::*;
+use iyes_progress::prelude::*;
+
+use super::tower_components::{TowerCooldown, TowerMarker, TrapCharges};
+use super::tower_health::Stunned;
+use super::tower_panel::SelectedTower;
+use super::tower_targeting::{can_target, pick_targets, TargetingMode};
+use super::tower_types::{Splash, TowerSpecial, TowerStats, TowerType};
+use crate::animation::{DespawnOnAnimationDone, FillAlphaLens, KnockbackLens};
+use crate::enemies::{
+    ApplyStatus, Burrowed, DamageEnemy, DamageType, EnemyHealth, EnemyMarker, EnemyPath, EnemyType,
+    Flying, Footprint, Hidden, StatusEffect,
+};
+use crate::grid_position::{GridPosition, TILE_SIZE};
+use crate::player::{AttackMode, EndTurnRequested};
+use crate::TurnState;
+
+/// How long are targets highlighted before towers attack on their own?
+const HIGHLIGHT_DURATION: Duration = Duration::from_millis(500);
+
+/// Time between each queued attack firing
+const SHOT_INTERVAL: Duration = Duration::from_millis(150);
+
+/// How long does a projectile take to reach its target?
+const PROJECTILE_DURATION: Duration = Duration::from_millis(250);
+
+/// How long the flash where a projectile hits lasts
+const IMPACT_DURATION: Duration = Duration::from_millis(200);
+
+/// How long enemies are pushed back for when hit
+const KNOCKBACK_DURATION: Duration = Duration::from_millis(200);
+
+/// How far enemies are pushed back when hit
+const KNOCKBACK_DISTANCE: f32 = 3.;
+
+/// A tower ordered to attack an enemy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuedAttack {
+    /// Tower doing the attack
+    pub tower: Entity,
+    /// Enemy being attacked
+    pub target: Entity,
+}
+
+/// Attacks that will happen this turn, in order
+#[derive(Debug, Clone, Default)]
+pub struct AttackQueue(pub Vec<QueuedAttack>);
+
+/// A projectile flying towards an enemy
+#[derive(Debug, Component, Clone, Copy)]
+pub struct Projectile {
+    /// Tower that fired it
+    pub tower: Entity,
+    /// Enemy it will hit
+    pub target: Entity,
+    /// Damage done when it hits
+    pub damage: u8,
+    /// What kind of damage it does
+    pub damage_type: DamageType,
+    /// Can the splash hit flying enemies?
+    pub anti_air: bool,
+    /// Direction it is flying in, used to knock the enemy back
+    pub direction: Vec2,
+    /// Area damaged around the target, if any
+    pub splash: Option<Splash>,
+    /// Status effect applied to every enemy hit
+    pub effect: Option<StatusEffect>,
+}
+
+/// Marker for the ring drawn around targeted enemies
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct TargetHighlightMarker;
+
+/// Marker for the outline drawn around enemies the selected tower can attack
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct ValidTargetMarker;
+
+/// Marker for the tiles drawn to preview a splash attack
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct BlastPreviewMarker;
+
+/// Are we in the player attack part of the turn, ordering attacks manually?
+fn ordering_manually(state: &CurrentState<TurnState>, attack_mode: &AttackMode) -> bool {
+    state.0 == TurnState::InTurn(crate::TurnPart::PlayerAttack)
+        && *attack_mode == AttackMode::Manual
+}
+
+/// Throw away attacks that were never fired at the end of the attack part of the turn
+pub fn clear_attack_queue(mut queue: ResMut<AttackQueue>) {
+    queue.0.clear();
+}
+
+/// Queue an attack from the selected tower on the clicked enemy
+pub fn queue_manual_attack(
+    mouse: Res<Input<MouseButton>>,
+    hovered: Res<crate::mouse_location::MouseGridPos>,
+    attack_mode: Res<AttackMode>,
+    selected: Res<SelectedTower>,
+    mut queue: ResMut<AttackQueue>,
+    towers: Query<
+        (
+            &TowerType,
+            &TowerStats,
+            &GridPosition,
+            &TowerCooldown,
+            &TargetingMode,
+        ),
+        (With<TowerMarker>, Without<TrapCharges>, Without<Stunned>),
+    >,
+    enemies: Query<
+        (
+            (
+                Entity,
+                &GridPosition,
+                &EnemyPath,
+                &EnemyHealth,
+                &EnemyType,
+                &Footprint,
+            ),
+            Option<&Flying>,
+        ),
+        (With<EnemyMarker>, Without<Hidden>, Without<Burrowed>),
+    >,
+) {
+    if *attack_mode != AttackMode::Manual || !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (tower, position) = match (selected.0, hovered.0) {
+        (Some(tower), Some(position)) => (tower, position),
+        _ => return,
+    };
+    let (tower_type, stats, tower_pos, cooldown, mode) = match towers.get(tower) {
+        Ok(tower) => tower,
+        Err(_) => return,
+    };
+
+    let queued: Vec<_> = queue
+        .0
+        .iter()
+        .filter(|attack| attack.tower == tower)
+        .map(|attack| attack.target)
+        .collect();
+    if cooldown.0 > 0 || !tower_type.attacks() || queued.len() >= stats.shots() {
+        return;
+    }
+
+    // Enemies can share a tile, so use the towers targeting mode to pick one of them
+    let on_tile = enemies
+        .iter()
+        .filter(|(_, flying)| can_target(*tower_type, *flying))
+        .map(|(enemy, _)| enemy)
+        .filter(|(enemy, enemy_pos, _, _, _, footprint)| {
+            footprint.covers(enemy_pos.0, position.0) && !queued.contains(enemy)
+        });
+    let target = pick_targets(*mode, *tower_pos, stats.range, on_tile)
+        .into_iter()
+        .next();
+
+    if let Some(target) = target {
+        queue.0.push(QueuedAttack { tower, target });
+    }
+}
+
+/// Cancel the queued attacks of the selected tower with right click, or all of them with backspace
+pub fn cancel_queued_attacks(
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    selected: Res<SelectedTower>,
+    mut queue: ResMut<AttackQueue>,
+) {
+    if keys.just_pressed(KeyCode::Back) {
+        queue.0.clear();
+    } else if mouse.just_pressed(MouseButton::Right) {
+        if let Some(tower) = selected.0 {
+            queue.0.retain(|attack| attack.tower != tower);
+        }
+    }
+}
+
+/// Draw a ring around every enemy that is going to be attacked
+pub fn show_queued_targets(
+    mut commands: Commands,
+    queue: Res<AttackQueue>,
+    highlights: Query<Entity, With<TargetHighlightMarker>>,
+    enemies: Query<(), With<EnemyMarker>>,
+) {
+    if !queue.is_changed() {
+        return;
+    }
+
+    highlights.for_each(|highlight| commands.entity(highlight).despawn_recursive());
+
+    let mut targets: Vec<_> = queue.0.iter().map(|attack| attack.target).collect();
+    targets.sort();
+    targets.dedup();
+    for target in targets {
+        if enemies.get(target).is_err() {
+            continue;
+        }
+        commands.entity(target).add_children(|parent| {
+            parent
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &shapes::Circle {
+                        radius: 10.,
+                        center: Vec2::ZERO,
+                    },
+                    DrawMode::Stroke(StrokeMode::new(Color::YELLOW, 1.5)),
+                    Transform::from_xyz(0., 0., 12.),
+                ))
+                .insert(TargetHighlightMarker);
+        });
+    }
+}
+
+/// Outline the enemies the selected tower can attack while ordering attacks
+pub fn show_valid_targets(
+    mut commands: Commands,
+    state: Res<CurrentState<TurnState>>,
+    attack_mode: Res<AttackMode>,
+    selected: Res<SelectedTower>,
+    queue: Res<AttackQueue>,
+    outlines: Query<Entity, With<ValidTargetMarker>>,
+    towers: Query<
+        (&TowerType, &TowerStats, &GridPosition, &TowerCooldown),
+        (With<TowerMarker>, Without<TrapCharges>, Without<Stunned>),
+    >,
+    enemies: Query<
+        (Entity, &GridPosition, &Footprint, Option<&Flying>),
+        (With<EnemyMarker>, Without<Hidden>, Without<Burrowed>),
+    >,
+) {
+    if !state.is_changed()
+        && !attack_mode.is_changed()
+        && !selected.is_changed()
+        && !queue.is_changed()
+    {
+        return;
+    }
+
+    outlines.for_each(|outline| commands.entity(outline).despawn_recursive());
+
+    if !ordering_manually(&state, &attack_mode) {
+        return;
+    }
+    let tower = match selected.0 {
+        Some(tower) => tower,
+        None => return,
+    };
+    let (tower_type, stats, tower_pos, cooldown) = match towers.get(tower) {
+        Ok(tower) => tower,
+        Err(_) => return,
+    };
+    let queued = queue
+        .0
+        .iter()
+        .filter(|attack| attack.tower == tower)
+        .count();
+    if cooldown.0 > 0 || !tower_type.attacks() || queued >= stats.shots() {
+        return;
+    }
+
+    for (enemy, enemy_pos, footprint, flying) in enemies.iter() {
+        if footprint.distance(*enemy_pos, *tower_pos) <= f32::from(stats.range)
+            && can_target(*tower_type, flying)
+        {
+            commands.entity(enemy).add_children(|parent| {
+                parent
+                    .spawn_bundle(GeometryBuilder::build_as(
+                        &shapes::Circle {
+                            radius: 9.,
+                            center: Vec2::ZERO,
+                        },
+                        DrawMode::Stroke(StrokeMode::new(Color::rgba(1., 1., 1., 0.6), 1.)),
+                        Transform::from_xyz(0., 0., 12.),
+                    ))
+                    .insert(ValidTargetMarker);
+            });
+        }
+    }
+}
+
+/// Show the area a splash tower would hit when hovering a tile in its range
+///
+/// Tiles are drawn more opaque the more damage they would take
+pub fn show_blast_preview(
+    mut commands: Commands,
+    mut shown: Local<Option<(Entity, GridPosition, TowerStats)>>,
+    hovered: Res<crate::mouse_location::MouseGridPos>,
+    selected: Res<SelectedTower>,
+    previews: Query<Entity, With<BlastPreviewMarker>>,
+    towers: Query<(&TowerStats, &GridPosition), With<TowerMarker>>,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+) {
+    let wanted = match (selected.0, hovered.0) {
+        (Some(tower), Some(position)) => towers
+            .get(tower)
+            .ok()
+            .filter(|(stats, tower_pos)| tower_pos.distance(position) <= f32::from(stats.range))
+            .map(|(stats, _)| (tower, position, *stats)),
+        _ => None,
+    };
+    if *shown == wanted {
+        return;
+    }
+    *shown = wanted;
+
+    previews.for_each(|preview| commands.entity(preview).despawn_recursive());
+
+    let (center, stats, splash) = match wanted {
+        Some((_, center, stats)) => match stats.special {
+            Some(TowerSpecial::Splash(splash)) => (center, stats, splash),
+            _ => return,
+        },
+        None => return,
+    };
+
+    let radius = i32::from(splash.radius);
+    commands
+        .entity(world_query.single())
+        .add_children(|parent| {
+            for x in -radius..=radius {
+                for y in -radius..=radius {
+                    let tile = GridPosition(center.0 + IVec2::new(x, y));
+                    let damage = splash.damage_at(stats.damage, center.tiles_between(tile));
+                    let alpha = 0.15 + 0.35 * f32::from(damage) / f32::from(stats.damage.max(1));
+                    parent
+                        .spawn_bundle(GeometryBuilder::build_as(
+                            &shapes::Rectangle {
+                                extents: Vec2::splat(TILE_SIZE),
+                                origin: RectangleOrigin::Center,
+                            },
+                            DrawMode::Outlined {
+                                fill_mode: FillMode::color(Color::rgba(1., 0.45, 0.1, alpha)),
+                                outline_mode: StrokeMode::new(Color::rgba(1., 0.45, 0.1, 0.8), 0.5),
+                            },
+                            Transform::from_translation(tile.to_world().extend(15.)),
+                        ))
+                        .insert(BlastPreviewMarker)
+                        .insert(crate::RemoveOnGameplayExit);
+                }
+            }
+        });
+}
+
+/// Fire the queued attacks, in order, once they are confirmed
+///
+/// Automatic attacks fire after their targets have been highlighted for a bit,
+/// manual attacks fire when the player ends their turn.
+/// The attack part of the turn is over when every projectile has hit.
+pub fn resolve_attacks(
+    mut commands: Commands,
+    global_timer: Res<Time>,
+    mut timer: Local<Timer>,
+    state: Res<CurrentState<TurnState>>,
+    attack_mode: Res<AttackMode>,
+    end_turn: Res<EndTurnRequested>,
+    mut queue: ResMut<AttackQueue>,
+    mut towers: Query<(&TowerType, &TowerStats, &Transform, &mut TowerCooldown)>,
+    enemies: Query<&Transform, (With<EnemyMarker>, Without<TowerMarker>)>,
+    projectiles: Query<(), With<Projectile>>,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+) -> Progress {
+    if state.is_changed() {
+        *timer = Timer::new(HIGHLIGHT_DURATION, false);
+    }
+    timer.tick(global_timer.delta());
+
+    let confirmed = match *attack_mode {
+        AttackMode::Automatic => timer.finished(),
+        AttackMode::Manual => end_turn.0,
+    };
+    if !confirmed {
+        return false.into();
+    }
+
+    if queue.0.is_empty() {
+        return projectiles.is_empty().into();
+    }
+
+    let world = world_query.single();
+    for (index, attack) in queue.0.drain(..).enumerate() {
+        let (tower_type, stats, tower_trans, mut cooldown) = match towers.get_mut(attack.tower) {
+            Ok(tower) => tower,
+            Err(_) => continue,
+        };
+        let damage_type = match tower_type.damage_type() {
+            Some(damage_type) => damage_type,
+            None => continue,
+        };
+        let target_trans = match enemies.get(attack.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        cooldown.0 = stats.cooldown;
+
+        let start = tower_trans.translation.truncate().extend(30.);
+        let end = target_trans.translation.truncate().extend(30.);
+        let flight = Tween::new(
+            EaseFunction::QuadraticIn,
+            TweeningType::Once,
+            PROJECTILE_DURATION,
+            TransformPositionLens { start, end },
+        );
+        let animation = if index == 0 {
+            Sequence::from_single(flight)
+        } else {
+            Delay::new(SHOT_INTERVAL * index as u32).then(flight)
+        };
+
+        commands.entity(world).add_children(|parent| {
+            parent
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &shapes::Circle {
+                        radius: 1.5,
+                        center: Vec2::ZERO,
+                    },
+                    DrawMode::Fill(FillMode::color(tower_type.color())),
+                    Transform::from_translation(start),
+                ))
+                .insert(Projectile {
+                    tower: attack.tower,
+                    target: attack.target,
+                    damage: stats.damage,
+                    damage_type,
+                    anti_air: tower_type.anti_air(),
+                    direction: (end - start).truncate().normalize_or_zero(),
+                    splash: match stats.special {
+                        Some(TowerSpecial::Splash(splash)) => Some(splash),
+                        _ => None,
+                    },
+                    effect: stats.effect,
+                })
+                .insert(Animator::new(animation))
+                .insert(crate::RemoveOnGameplayExit);
+        });
+    }
+
+    // The projectiles only exsist next frame
+    false.into()
+}
+
+/// Spawn a quick flash, used where attacks land
+pub fn spawn_impact_flash(parent: &mut ChildBuilder, translation: Vec3, color: Color) {
+    parent
+        .spawn_bundle(GeometryBuilder::build_as(
+            &shapes::Circle {
+                radius: 4.,
+                center: Vec2::ZERO,
+            },
+            DrawMode::Fill(FillMode::color(color)),
+            Transform::from_translation(translation),
+        ))
+        .insert(Animator::new(Tween::new(
+            EaseFunction::QuadraticOut,
+            TweeningType::Once,
+            IMPACT_DURATION,
+            TransformScaleLens {
+                start: Vec3::splat(0.5),
+                end: Vec3::splat(1.5),
+            },
+        )))
+        .insert(Animator::new(Tween::new(
+            EaseFunction::QuadraticIn,
+            TweeningType::Once,
+            IMPACT_DURATION,
+            FillAlphaLens {
+                color,
+                start: 0.9,
+                end: 0.,
+            },
+        )))
+        .insert(DespawnOnAnimationDone)
+        .insert(crate::RemoveOnGameplayExit);
+}
+
+/// Damage the target once the projectile reaches it, with a flash and knocking it back
+pub fn projectile_hit(
+    mut commands: Commands,
+    query: Query<(Entity, &Projectile, &Transform, &Animator<Transform>)>,
+    enemies: Query<
+        (
+            Entity,
+            &Transform,
+            &GridPosition,
+            Option<&Animator<Transform>>,
+            &Footprint,
+            Option<&Flying>,
+            Option<&Hidden>,
+        ),
+        (With<EnemyMarker>, Without<Projectile>, Without<Burrowed>),
+    >,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+    mut damage_events: EventWriter<DamageEnemy>,
+    mut status_events: EventWriter<ApplyStatus>,
+) {
+    for (entity, projectile, trans, animator) in query.iter() {
+        if animator.progress() < 1. {
+            continue;
+        }
+
+        let mut hit = |target, amount| {
+            damage_events.send(DamageEnemy {
+                target,
+                amount,
+                damage_type: projectile.damage_type,
+                source: Some(projectile.tower),
+            });
+            if let Some(effect) = projectile.effect {
+                status_events.send(ApplyStatus { target, effect });
+            }
+        };
+        if let Some(splash) = projectile.splash {
+            // Hit everything around where the projectile landed, even if the target is already gone
+            // Burrowed enemies are left out by the query, they are safe underground
+            let impact = GridPosition::from_world(trans.translation.truncate());
+            for (enemy, _, enemy_pos, _, footprint, flying, hidden) in enemies.iter() {
+                // Undetected stealthy enemies cant be hit, just like they cant be targeted
+                if (flying.is_some() && !projectile.anti_air) || hidden.is_some() {
+                    continue;
+                }
+                let amount = splash.damage_at(
+                    projectile.damage,
+                    footprint.tiles_between(*enemy_pos, impact),
+                );
+                if amount > 0 {
+                    hit(enemy, amount);
+                }
+            }
+        } else {
+            hit(projectile.target, projectile.damage);
+        }
+        commands.entity(entity).despawn_recursive();
+
+        commands
+            .entity(world_query.single())
+            .add_children(|parent| {
+                spawn_impact_flash(parent, trans.translation, Color::WHITE);
+            });
+
+        let (_, enemy_trans, _, enemy_animator, ..) = match enemies.get(projectile.target) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+        // Dont knock back enemies that are already moving, or they would not end up where they started
+        if enemy_animator.map_or(false, |animator| animator.progress() < 1.) {
+            continue;
+        }
+        commands
+            .entity(projectile.target)
+            .insert(Animator::new(Tween::new(
+                EaseFunction::QuadraticOut,
+                TweeningType::Once,
+                KNOCKBACK_DURATION,
+                KnockbackLens {
+                    translation: enemy_trans.translation,
+                    scale: enemy_trans.scale,
+                    offset: (projectile.direction * KNOCKBACK_DISTANCE).extend(0.),
+                    squash: 0.25,
+                },
+            )));
+    }
+}
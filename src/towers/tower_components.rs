@@ -0,0 +1,85 @@
+//! Core tower components and bundles
+
+use bevy::prelude::*;
+
+use super::tower_targeting::TargetingMode;
+use super::tower_types::{TowerStats, TowerType};
+use crate::grid_position::GridPosition;
+
+/// All towers will have this component
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct TowerMarker;
+
+/// How many turns until this tower can attack again?
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct TowerCooldown(pub u8);
+
+/// How many times has this tower been upgraded?
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct TowerLevel(pub usize);
+
+/// How much gold has been spent on this tower, used to calculate the sell price
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct TowerInvested(pub u32);
+
+/// How many more times can this trap trigger?
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct TrapCharges(pub u8);
+
+/// Bundle containing everything a tower will need
+#[derive(Bundle)]
+pub struct TowerBundle {
+    /// Marker so we know this is a tower
+    pub _m: TowerMarker,
+    /// Make us be able to reconise a tower!
+    pub _name: Name,
+
+    /// Give the tower a position in the world, the visuals are child entities
+    #[bundle]
+    pub _spatial: SpatialBundle,
+
+    /// What type is this tower?
+    pub tower_type: TowerType,
+
+    /// What tile is the tower built on?
+    pub grid_location: GridPosition,
+
+    /// Turns left until the tower can attack
+    pub cooldown: TowerCooldown,
+
+    /// Current upgrade level
+    pub level: TowerLevel,
+
+    /// Stats for the current level
+    pub stats: TowerStats,
+
+    /// Gold spent on building and upgrading
+    pub invested: TowerInvested,
+
+    /// Which enemies should the tower attack first?
+    pub targeting: TargetingMode,
+
+    /// Towers should despawn when the gameplay section of the game is over
+    pub _cleanup: crate::RemoveOnGameplayExit,
+}
+
+impl TowerBundle {
+    /// Create a tower of the given type on a tile
+    pub fn new(tower_type: TowerType, grid_location: GridPosition) -> Self {
+        Self {
+            _m: TowerMarker,
+            _name: Name::new(tower_type.name()),
+            _spatial: SpatialBundle::from_transform(Transform::from_translation(
+                grid_location.to_world().extend(5.),
+            )),
+            tower_type,
+            grid_location,
+            cooldown: TowerCooldown(0),
+            level: TowerLevel(0),
+            stats: tower_type.base_stats(),
+            invested: TowerInvested(tower_type.cost()),
+            targeting: TargetingMode::default(),
+            _cleanup: crate::RemoveOnGameplayExit,
+        }
+    }
+}
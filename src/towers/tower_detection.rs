@@ -0,0 +1,50 @@
+//! Detection towers reveal stealthy enemies, so the other towers can attack them
+
+use bevy::prelude::*;
+
+use super::tower_components::TowerMarker;
+use super::tower_types::{TowerStats, TowerType};
+use crate::enemies::{EnemyMarker, Hidden, Revealed, Stealth};
+use crate::grid_position::GridPosition;
+
+/// Reveal stealthy enemies in range of a detection tower, and hide the ones no tower can see
+///
+/// [`Revealed`] enemies stay detected wherever they are until it runs out.
+/// This runs once at the start of the player turn, when enemies are done moving
+pub fn detect_stealthy_enemies(
+    mut commands: Commands,
+    towers: Query<(&TowerType, &TowerStats, &GridPosition), With<TowerMarker>>,
+    mut enemies: Query<
+        (
+            Entity,
+            &GridPosition,
+            Option<&Hidden>,
+            Option<&mut Revealed>,
+        ),
+        (With<EnemyMarker>, With<Stealth>),
+    >,
+) {
+    for (enemy, enemy_pos, hidden, revealed) in enemies.iter_mut() {
+        let mut detected = towers.iter().any(|(tower_type, stats, tower_pos)| {
+            tower_type.detects_stealth() && tower_pos.distance(*enemy_pos) <= f32::from(stats.range)
+        });
+        if let Some(mut revealed) = revealed {
+            revealed.0 = revealed.0.saturating_sub(1);
+            if revealed.0 == 0 {
+                commands.entity(enemy).remove::<Revealed>();
+            } else {
+                detected = true;
+            }
+        }
+
+        match (detected, hidden.is_some()) {
+            (true, true) => {
+                commands.entity(enemy).remove::<Hidden>();
+            }
+            (false, false) => {
+                commands.entity(enemy).insert(Hidden);
+            }
+            _ => {}
+        }
+    }
+}
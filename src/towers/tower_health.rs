@@ -0,0 +1,234 @@
+//! Tower health, and enemies that attack or stun towers at the end of the enemy turn
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use bevy_tweening::{Animator, EaseFunction, Tween, TweeningType};
+
+use super::tower_attacks::spawn_impact_flash;
+use super::tower_components::TowerMarker;
+use crate::animation::KnockbackLens;
+use crate::enemies::{EnemyAbility, EnemyMarker, EnemyRegistry, EnemyType, Footprint};
+use crate::grid_position::GridPosition;
+
+/// How long an enemy takes to lunge at a tower and back
+const LUNGE_DURATION: Duration = Duration::from_millis(250);
+
+/// How much health does this tower have left? Traps cant be attacked, so they dont have any
+#[derive(Component, Debug, Clone, Copy)]
+pub struct TowerHealth(pub u8);
+
+/// How many player attacks does this tower still have to skip?
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Stunned(pub u8);
+
+/// Marker for the icon shown above stunned towers
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct StunIconMarker;
+
+/// Spawn the health bar of a tower
+pub fn spawn_tower_healthbar(parent: &mut ChildBuilder, max_health: u8) {
+    let settings = crate::track_bar::TrackbarSettings {
+        total: usize::from(max_health),
+        width: 12.,
+        filled_color: Color::rgba(0.2, 0.8, 0.2, 0.7),
+        secondary_color: Color::NONE,
+        background_color: Color::rgba(0., 0., 0., 0.8),
+    };
+    parent
+        .spawn_bundle(crate::track_bar::TrackbarBundle {
+            settings,
+            progress: crate::track_bar::TrackbarProgess(usize::from(max_health)),
+            position: SpatialBundle {
+                transform: Transform {
+                    translation: Vec3::new(0., -7., 3.),
+                    ..default()
+                },
+                ..default()
+            },
+            ..default()
+        })
+        .add_children(|parent| {
+            crate::track_bar::TrackbarBundle::create_children(&settings, parent);
+        });
+}
+
+/// Enemies that can attack or stun towers go after the closest tower in their range
+pub fn enemies_attack_towers(
+    mut commands: Commands,
+    enemies: Query<
+        (
+            Entity,
+            &Transform,
+            &GridPosition,
+            &Footprint,
+            &EnemyType,
+            Option<&Animator<Transform>>,
+        ),
+        With<EnemyMarker>,
+    >,
+    mut towers: Query<
+        (
+            Entity,
+            &Transform,
+            &GridPosition,
+            &mut TowerHealth,
+            Option<&mut Stunned>,
+        ),
+        (With<TowerMarker>, Without<EnemyMarker>),
+    >,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+    registry: Res<EnemyRegistry>,
+) {
+    for (enemy, enemy_trans, enemy_pos, footprint, enemy_type, animator) in enemies.iter() {
+        for ability in registry.abilities(*enemy_type) {
+            let range = match ability {
+                EnemyAbility::AttackTowers { range, .. }
+                | EnemyAbility::StunTowers { range, .. } => f32::from(*range),
+                _ => continue,
+            };
+
+            let distance = |tower_pos: &GridPosition| footprint.distance(*enemy_pos, *tower_pos);
+            let target = towers
+                .iter_mut()
+                .filter(|(_, _, tower_pos, ..)| distance(tower_pos) <= range)
+                .min_by(|(_, _, a, ..), (_, _, b, ..)| distance(a).total_cmp(&distance(b)));
+            let (tower, tower_trans, _, mut health, stunned) = match target {
+                Some(tower) => tower,
+                None => continue,
+            };
+
+            let color = match ability {
+                EnemyAbility::AttackTowers { damage, .. } => {
+                    health.0 = health.0.saturating_sub(*damage);
+                    Color::rgb(1., 0.3, 0.2)
+                }
+                EnemyAbility::StunTowers { turns, .. } => {
+                    let turns = stunned.map_or(*turns, |stunned| stunned.0.max(*turns));
+                    commands.entity(tower).insert(Stunned(turns));
+                    Color::rgb(1., 0.9, 0.3)
+                }
+                _ => continue,
+            };
+
+            commands
+                .entity(world_query.single())
+                .add_children(|parent| {
+                    spawn_impact_flash(
+                        parent,
+                        tower_trans.translation.truncate().extend(30.),
+                        color,
+                    );
+                });
+
+            // Dont lunge while still moving or spawning, the lunge would replace that tween
+            if animator.map_or(false, |animator| animator.progress() < 1.) {
+                continue;
+            }
+            // Lunge at the tower and back
+            let direction = (tower_trans.translation - enemy_trans.translation)
+                .truncate()
+                .normalize_or_zero();
+            commands.entity(enemy).insert(Animator::new(Tween::new(
+                EaseFunction::QuadraticInOut,
+                TweeningType::Once,
+                LUNGE_DURATION,
+                KnockbackLens {
+                    translation: enemy_trans.translation,
+                    scale: enemy_trans.scale,
+                    offset: (direction * 5.).extend(0.),
+                    squash: -0.1,
+                },
+            )));
+        }
+    }
+}
+
+/// Keep the health bars of towers up to date
+pub fn update_tower_healthbar(
+    query: Query<(&TowerHealth, &Children), Changed<TowerHealth>>,
+    mut bar_query: Query<&mut crate::track_bar::TrackbarProgess>,
+) {
+    for (health, children) in query.iter() {
+        for child in children.iter() {
+            if let Ok(mut progress) = bar_query.get_mut(*child) {
+                progress.0 = usize::from(health.0);
+            }
+        }
+    }
+}
+
+/// Towers without any health left are destroyed
+pub fn destroy_towers(
+    mut commands: Commands,
+    query: Query<(Entity, &TowerHealth, &Transform), Changed<TowerHealth>>,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+) {
+    for (tower, health, trans) in query.iter() {
+        if health.0 > 0 {
+            continue;
+        }
+
+        commands.entity(tower).despawn_recursive();
+        commands
+            .entity(world_query.single())
+            .add_children(|parent| {
+                spawn_impact_flash(
+                    parent,
+                    trans.translation.truncate().extend(30.),
+                    Color::rgb(0.4, 0.4, 0.4),
+                );
+            });
+    }
+}
+
+/// Count down stuns once the player attack they made the tower skip is over
+pub fn recover_from_stun(mut commands: Commands, mut query: Query<(Entity, &mut Stunned)>) {
+    for (tower, mut stunned) in query.iter_mut() {
+        stunned.0 = stunned.0.saturating_sub(1);
+        if stunned.0 == 0 {
+            commands.entity(tower).remove::<Stunned>();
+        }
+    }
+}
+
+/// Show an icon above stunned towers, and remove it once they recover
+pub fn show_stun_icons(
+    mut commands: Commands,
+    stunned: Query<Entity, Added<Stunned>>,
+    recovered: RemovedComponents<Stunned>,
+    children_query: Query<&Children>,
+    icon_query: Query<(), With<StunIconMarker>>,
+) {
+    for tower in stunned.iter() {
+        commands.entity(tower).with_children(|parent| {
+            parent
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &shapes::RegularPolygon {
+                        sides: 5,
+                        feature: shapes::RegularPolygonFeature::Radius(2.5),
+                        center: Vec2::ZERO,
+                    },
+                    DrawMode::Outlined {
+                        fill_mode: FillMode::color(Color::rgb(1., 0.9, 0.3)),
+                        outline_mode: StrokeMode::new(Color::BLACK, 0.5),
+                    },
+                    Transform::from_xyz(4., 6., 4.),
+                ))
+                .insert(StunIconMarker);
+        });
+    }
+
+    for tower in recovered.iter() {
+        let children = match children_query.get(tower) {
+            Ok(children) => children,
+            Err(_) => continue,
+        };
+        for child in children.iter() {
+            if icon_query.get(*child).is_ok() {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+    }
+}
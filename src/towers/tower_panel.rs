@@ -0,0 +1,580 @@
+//! Context panel for the selected tower, used to upgrade and sell towers
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use iyes_loopless::prelude::*;
+
+use super::tower_components::{TowerInvested, TowerLevel, TowerMarker, TrapCharges};
+use super::tower_health::{Stunned, TowerHealth};
+use super::tower_placement::PlacementMode;
+use super::tower_targeting::TargetingMode;
+use super::tower_types::{TowerStats, TowerType, TowerVisualMarker};
+use crate::enemies::EnemyRegistry;
+use crate::grid_position::{GridPosition, TILE_SIZE};
+use crate::player::{AttackMode, PlayerGold};
+use crate::ui::BottomBarMarker;
+use crate::{TurnPart, TurnState};
+
+/// Color of a panel button the player can use
+const AVAILABLE_COLOR: Color = Color::rgb(0.4, 0.4, 0.4);
+/// Color of a panel button the mouse is over
+const HOVERED_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
+/// Color of a panel button the player cant use right now
+const UNAVAILABLE_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+
+/// Settings for buying and selling towers
+#[derive(Debug, Clone, Copy)]
+pub struct TowerConfig {
+    /// How many percent of the gold spent on a tower do you get back when selling it?
+    pub sell_refund_percent: u32,
+}
+
+impl Default for TowerConfig {
+    fn default() -> Self {
+        Self {
+            sell_refund_percent: 70,
+        }
+    }
+}
+
+/// The tower the player has clicked on, if any
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SelectedTower(pub Option<Entity>);
+
+/// Marker for the root of the tower panel
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct TowerPanelMarker;
+
+/// Marker for the text describing the selected tower
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct TowerInfoText;
+
+/// Marker for the upgrade button
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct UpgradeButton;
+
+/// Marker for the text on the upgrade button
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct UpgradeButtonText;
+
+/// Marker for the sell button
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct SellButton;
+
+/// Marker for the text on the sell button
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct SellButtonText;
+
+/// Marker for the button cycling through targeting modes
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct TargetingButton;
+
+/// Marker for the text on the targeting button
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct TargetingButtonText;
+
+/// Marker for the range preview around the selected tower
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct SelectionRangeMarker;
+
+/// Gold we get back from selling a tower
+fn sell_price(invested: &TowerInvested, config: &TowerConfig) -> u32 {
+    invested.0 * config.sell_refund_percent / 100
+}
+
+/// Can the player change their towers right now?
+fn in_player_action(state: &CurrentState<TurnState>) -> bool {
+    state.0 == TurnState::InTurn(TurnPart::PlayerAction)
+}
+
+/// Add the tower panel to the bottom bar once it exists
+pub fn create_tower_panel(
+    mut commands: Commands,
+    bar_query: Query<Entity, Added<BottomBarMarker>>,
+    assets: Res<crate::assets::MiscAssets>,
+) {
+    for bar in bar_query.iter() {
+        let text_style = TextStyle {
+            font: assets.font.clone_weak(),
+            font_size: 14.,
+            color: Color::WHITE,
+        };
+        let button_style = Style {
+            size: Size::new(Val::Px(112.), Val::Px(24.)),
+            margin: UiRect::all(Val::Px(2.)),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        };
+
+        commands.entity(bar).add_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(240.), Val::Px(128.)),
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            right: Val::Px(128.),
+                            bottom: Val::Px(4.),
+                            ..default()
+                        },
+                        flex_direction: FlexDirection::ColumnReverse,
+                        padding: UiRect::all(Val::Px(4.)),
+                        display: Display::None,
+                        ..default()
+                    },
+                    color: Color::rgb(0.25, 0.25, 0.25).into(),
+                    ..default()
+                })
+                .insert(TowerPanelMarker)
+                .insert(Name::new("Tower Panel"))
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle::from_section("", text_style.clone()))
+                        .insert(TowerInfoText);
+
+                    // Buttons
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            color: Color::NONE.into(),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent
+                                .spawn_bundle(ButtonBundle {
+                                    style: button_style.clone(),
+                                    color: AVAILABLE_COLOR.into(),
+                                    ..default()
+                                })
+                                .insert(UpgradeButton)
+                                .with_children(|parent| {
+                                    parent
+                                        .spawn_bundle(TextBundle::from_section(
+                                            "",
+                                            text_style.clone(),
+                                        ))
+                                        .insert(UpgradeButtonText);
+                                });
+                            parent
+                                .spawn_bundle(ButtonBundle {
+                                    style: button_style.clone(),
+                                    color: AVAILABLE_COLOR.into(),
+                                    ..default()
+                                })
+                                .insert(SellButton)
+                                .with_children(|parent| {
+                                    parent
+                                        .spawn_bundle(TextBundle::from_section(
+                                            "",
+                                            text_style.clone(),
+                                        ))
+                                        .insert(SellButtonText);
+                                });
+                        });
+                    parent
+                        .spawn_bundle(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(228.), Val::Px(24.)),
+                                ..button_style.clone()
+                            },
+                            color: AVAILABLE_COLOR.into(),
+                            ..default()
+                        })
+                        .insert(TargetingButton)
+                        .with_children(|parent| {
+                            parent
+                                .spawn_bundle(TextBundle::from_section("", text_style.clone()))
+                                .insert(TargetingButtonText);
+                        });
+                });
+        });
+    }
+}
+
+/// Select the tower under the mouse when the player clicks
+pub fn select_tower(
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    hovered: Res<crate::mouse_location::MouseGridPos>,
+    mode: Res<PlacementMode>,
+    state: Res<CurrentState<TurnState>>,
+    attack_mode: Res<AttackMode>,
+    towers: Query<(Entity, &GridPosition), With<TowerMarker>>,
+    mut selected: ResMut<SelectedTower>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        selected.0 = None;
+    }
+
+    // Clicking while placing a tower is used to build the tower
+    // And clicks on the ui dont have a hovered tile
+    if !mouse.just_pressed(MouseButton::Left) || mode.0.is_some() {
+        return;
+    }
+    if let Some(position) = hovered.0 {
+        let clicked_tower = towers
+            .iter()
+            .find(|(_, tower_pos)| **tower_pos == position)
+            .map(|(tower, _)| tower);
+
+        // While ordering attacks clicking a enemy queues an attack from the selected tower
+        let ordering_attacks = state.0 == TurnState::InTurn(TurnPart::PlayerAttack)
+            && *attack_mode == AttackMode::Manual;
+        if clicked_tower.is_some() || !ordering_attacks {
+            selected.0 = clicked_tower;
+        }
+    }
+}
+
+/// Deselect the selected tower if it no longer exsists
+pub fn clear_removed_selection(
+    mut selected: ResMut<SelectedTower>,
+    towers: Query<(), With<TowerMarker>>,
+) {
+    if let Some(tower) = selected.0 {
+        if towers.get(tower).is_err() {
+            selected.0 = None;
+        }
+    }
+}
+
+/// Draw the range of the selected tower
+pub fn show_selection_range(
+    mut commands: Commands,
+    selected: Res<SelectedTower>,
+    changed_stats: Query<&TowerStats, Changed<TowerStats>>,
+    all_stats: Query<&TowerStats>,
+    range_query: Query<Entity, With<SelectionRangeMarker>>,
+) {
+    let stats_changed = selected
+        .0
+        .map_or(false, |tower| changed_stats.get(tower).is_ok());
+    if !selected.is_changed() && !stats_changed {
+        return;
+    }
+
+    range_query.for_each(|range| commands.entity(range).despawn_recursive());
+
+    if let Some(tower) = selected.0 {
+        if let Ok(stats) = all_stats.get(tower) {
+            commands.entity(tower).add_children(|parent| {
+                parent
+                    .spawn_bundle(GeometryBuilder::build_as(
+                        &shapes::Circle {
+                            radius: f32::from(stats.range) * TILE_SIZE,
+                            center: Vec2::ZERO,
+                        },
+                        DrawMode::Stroke(StrokeMode::new(Color::rgba(1., 1., 1., 0.7), 1.)),
+                        Transform::from_xyz(0., 0., 20.),
+                    ))
+                    .insert(SelectionRangeMarker);
+            });
+        }
+    }
+}
+
+/// Show the panel for the selected tower and keep its text up to date
+pub fn update_tower_panel(
+    selected: Res<SelectedTower>,
+    config: Res<TowerConfig>,
+    gold: Res<PlayerGold>,
+    state: Res<CurrentState<TurnState>>,
+    registry: Res<EnemyRegistry>,
+    towers: Query<(
+        &TowerType,
+        &TowerLevel,
+        &TowerStats,
+        &TowerInvested,
+        &TargetingMode,
+        Option<&TrapCharges>,
+        Option<&TowerHealth>,
+        Option<&Stunned>,
+    )>,
+    mut panel_query: Query<&mut Style, (With<TowerPanelMarker>, Without<TargetingButton>)>,
+    mut targeting_style_query: Query<
+        &mut Style,
+        (With<TargetingButton>, Without<TowerPanelMarker>),
+    >,
+    mut info_query: Query<
+        &mut Text,
+        (
+            With<TowerInfoText>,
+            Without<UpgradeButtonText>,
+            Without<SellButtonText>,
+            Without<TargetingButtonText>,
+        ),
+    >,
+    mut upgrade_text_query: Query<
+        &mut Text,
+        (
+            With<UpgradeButtonText>,
+            Without<SellButtonText>,
+            Without<TargetingButtonText>,
+        ),
+    >,
+    mut sell_text_query: Query<
+        &mut Text,
+        (
+            With<SellButtonText>,
+            Without<UpgradeButtonText>,
+            Without<TargetingButtonText>,
+        ),
+    >,
+    mut targeting_text_query: Query<
+        &mut Text,
+        (
+            With<TargetingButtonText>,
+            Without<UpgradeButtonText>,
+            Without<SellButtonText>,
+        ),
+    >,
+    mut button_query: Query<
+        (
+            &Interaction,
+            &mut UiColor,
+            Option<&UpgradeButton>,
+            Option<&SellButton>,
+        ),
+        Or<(With<UpgradeButton>, With<SellButton>, With<TargetingButton>)>,
+    >,
+) {
+    let tower = selected.0.and_then(|tower| towers.get(tower).ok());
+
+    for mut style in panel_query.iter_mut() {
+        style.display = if tower.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+
+    let (tower_type, level, stats, invested, targeting, charges, health, stunned) = match tower {
+        Some(tower) => tower,
+        None => return,
+    };
+    let trap = tower_type.trap().zip(charges);
+    let health = match (health, tower_type.max_health()) {
+        (Some(health), Some(max_health)) => format!("\nHealth: {}/{}", health.0, max_health),
+        _ => String::new(),
+    };
+    let stunned = stunned.map_or_else(String::new, |stunned| {
+        format!("\nStunned, skips {} attack(s)", stunned.0)
+    });
+
+    // Traps trigger on their own and watchtowers dont attack, so there is nothing to target
+    for mut style in targeting_style_query.iter_mut() {
+        style.display = if tower_type.attacks() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+
+    for mut text in info_query.iter_mut() {
+        if let Some((trap, charges)) = trap {
+            let damage = if stats.damage > 0 {
+                format!(
+                    "  Damage: {} {}",
+                    stats.damage,
+                    tower_type
+                        .damage_type()
+                        .map_or("", |damage_type| damage_type.name())
+                )
+            } else {
+                String::new()
+            };
+            let effect = stats
+                .effect
+                .map_or_else(String::new, |effect| format!("\n{}", effect.describe()));
+            text.sections[0].value = format!(
+                "{}\nCharges: {}/{}{}\n{}{}",
+                tower_type.name(),
+                charges.0,
+                trap.charges,
+                damage,
+                trap.describe(),
+                effect,
+            );
+            continue;
+        }
+
+        if tower_type.detects_stealth() {
+            text.sections[0].value = format!(
+                "{} (level {})\nRange: {}{}{}\nReveals stealthy enemies in range",
+                tower_type.name(),
+                level.0 + 1,
+                stats.range,
+                health,
+                stunned,
+            );
+            continue;
+        }
+
+        let mut info = format!(
+            "{} (level {})\nRange: {}  Damage: {} {}  Cooldown: {}{}{}",
+            tower_type.name(),
+            level.0 + 1,
+            stats.range,
+            stats.damage,
+            tower_type
+                .damage_type()
+                .map_or("", |damage_type| damage_type.name()),
+            stats.cooldown,
+            health,
+            stunned,
+        );
+        if let Some(special) = stats.special {
+            info.push('\n');
+            info.push_str(&special.describe());
+        }
+        if let Some(effect) = stats.effect {
+            info.push('\n');
+            info.push_str(&effect.describe());
+        }
+        if tower_type.anti_air() {
+            info.push_str("\nHits flying enemies");
+        }
+        text.sections[0].value = info;
+    }
+
+    let upgrade = tower_type.next_upgrade(level.0);
+    // Traps use the upgrade button to re-arm
+    let rearm_cost =
+        trap.and_then(|(trap, charges)| trap.rearm_cost.filter(|_| charges.0 < trap.charges));
+    for mut text in upgrade_text_query.iter_mut() {
+        text.sections[0].value = match (trap, rearm_cost, upgrade) {
+            (Some(_), Some(cost), _) => format!("Re-arm ({cost} gold)"),
+            (Some((trap, _)), None, _) if trap.rearm_cost.is_none() => "Single use".to_owned(),
+            (Some(_), None, _) => "Armed".to_owned(),
+            (None, _, Some(upgrade)) => format!("Upgrade ({} gold)", upgrade.cost),
+            (None, _, None) => "Max level".to_owned(),
+        };
+    }
+    for mut text in sell_text_query.iter_mut() {
+        text.sections[0].value = format!("Sell (+{} gold)", sell_price(invested, &config));
+    }
+    for mut text in targeting_text_query.iter_mut() {
+        text.sections[0].value = format!("Target: {}", targeting.name(&registry));
+    }
+
+    let cost = match trap {
+        Some(_) => rearm_cost,
+        None => upgrade.map(|upgrade| upgrade.cost),
+    };
+    let can_upgrade = in_player_action(&state) && cost.map_or(false, |cost| gold.0 >= cost);
+    let can_sell = in_player_action(&state);
+    for (interaction, mut color, upgrade_button, sell_button) in button_query.iter_mut() {
+        let available = match (upgrade_button, sell_button) {
+            (Some(_), _) => can_upgrade,
+            (_, Some(_)) => can_sell,
+            // Targeting can be changed at any time
+            _ => true,
+        };
+        color.0 = button_color(available, *interaction);
+    }
+}
+
+/// Color of a panel button
+fn button_color(available: bool, interaction: Interaction) -> Color {
+    match (available, interaction) {
+        (false, _) => UNAVAILABLE_COLOR,
+        (true, Interaction::None) => AVAILABLE_COLOR,
+        (true, _) => HOVERED_COLOR,
+    }
+}
+
+/// Upgrade the selected tower when the upgrade button is clicked
+pub fn upgrade_tower(
+    mut commands: Commands,
+    button_query: Query<&Interaction, (With<UpgradeButton>, Changed<Interaction>)>,
+    selected: Res<SelectedTower>,
+    state: Res<CurrentState<TurnState>>,
+    mut gold: ResMut<PlayerGold>,
+    mut towers: Query<(
+        &TowerType,
+        &mut TowerLevel,
+        &mut TowerStats,
+        &mut TowerInvested,
+        &Children,
+    )>,
+    visual_query: Query<(), With<TowerVisualMarker>>,
+) {
+    let clicked = button_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !clicked || !in_player_action(&state) {
+        return;
+    }
+
+    let tower = match selected.0 {
+        Some(tower) => tower,
+        None => return,
+    };
+    if let Ok((tower_type, mut level, mut stats, mut invested, children)) = towers.get_mut(tower) {
+        let upgrade = match tower_type.next_upgrade(level.0) {
+            Some(upgrade) if gold.0 >= upgrade.cost => upgrade,
+            _ => return,
+        };
+
+        gold.0 -= upgrade.cost;
+        invested.0 += upgrade.cost;
+        level.0 += 1;
+        *stats = upgrade.stats;
+
+        // Redraw the tower so it shows its new tier
+        for child in children.iter() {
+            if visual_query.get(*child).is_ok() {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+        let (tower_type, level) = (*tower_type, level.0);
+        commands
+            .entity(tower)
+            .with_children(|parent| tower_type.spawn_visuals(parent, 1., level));
+    }
+}
+
+/// Sell the selected tower when the sell button is clicked
+pub fn sell_tower(
+    mut commands: Commands,
+    button_query: Query<&Interaction, (With<SellButton>, Changed<Interaction>)>,
+    mut selected: ResMut<SelectedTower>,
+    state: Res<CurrentState<TurnState>>,
+    config: Res<TowerConfig>,
+    mut gold: ResMut<PlayerGold>,
+    towers: Query<&TowerInvested>,
+) {
+    let clicked = button_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !clicked || !in_player_action(&state) {
+        return;
+    }
+
+    if let Some(tower) = selected.0 {
+        if let Ok(invested) = towers.get(tower) {
+            gold.0 += sell_price(invested, &config);
+            commands.entity(tower).despawn_recursive();
+            selected.0 = None;
+        }
+    }
+}
+
+/// Switch to the next targeting mode when the targeting button is clicked
+pub fn cycle_targeting(
+    button_query: Query<&Interaction, (With<TargetingButton>, Changed<Interaction>)>,
+    selected: Res<SelectedTower>,
+    registry: Res<EnemyRegistry>,
+    mut towers: Query<&mut TargetingMode>,
+) {
+    let clicked = button_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !clicked {
+        return;
+    }
+
+    if let Some(mut targeting) = selected.0.and_then(|tower| towers.get_mut(tower).ok()) {
+        *targeting = targeting.next(&registry);
+    }
+}
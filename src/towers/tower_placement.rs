@@ -0,0 +1,188 @@
+//! Placing new towers on the map
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use iyes_loopless::prelude::*;
+
+use super::tower_components::{TowerBundle, TowerMarker, TrapCharges};
+use super::tower_health::{spawn_tower_healthbar, TowerHealth};
+use super::tower_types::TowerType;
+use crate::grid_position::{GridPosition, TILE_SIZE};
+use crate::ldtk_loader::{get_tile_type_at, get_tilemap};
+use crate::player::PlayerGold;
+use crate::{TurnPart, TurnState};
+
+/// Which tower is the player about to place, if any?
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlacementMode(pub Option<TowerType>);
+
+/// Marker for the see through tower following the mouse while placing
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct GhostTowerMarker;
+
+/// Marker for the range preview of the ghost tower
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct GhostRangeMarker;
+
+/// Can the player buy this tower right now?
+pub fn can_buy(tower_type: TowerType, state: TurnState, gold: &PlayerGold) -> bool {
+    state == TurnState::InTurn(TurnPart::PlayerAction) && gold.0 >= tower_type.cost()
+}
+
+/// Can this tower be built on this tile?
+fn can_place_at(
+    tower_type: TowerType,
+    position: GridPosition,
+    tilemap: &bevy_ecs_ldtk::prelude::LayerInstance,
+    towers: &Query<&GridPosition, With<TowerMarker>>,
+) -> bool {
+    tower_type.can_build_on(get_tile_type_at(tilemap, position.0))
+        && towers.iter().all(|tower_pos| *tower_pos != position)
+}
+
+/// Select a tower using its hotkey, or cancel placing with escape/right click
+pub fn placement_hotkeys(
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    state: Res<CurrentState<TurnState>>,
+    gold: Res<PlayerGold>,
+    mut mode: ResMut<PlacementMode>,
+) {
+    for tower_type in TowerType::ALL {
+        if keys.just_pressed(tower_type.hotkey()) && can_buy(tower_type, state.0, &gold) {
+            mode.0 = Some(tower_type);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Escape) || mouse.just_pressed(MouseButton::Right) {
+        mode.0 = None;
+    }
+}
+
+/// Stop placing when we leave the player action part of the turn
+pub fn cancel_placement(mut mode: ResMut<PlacementMode>) {
+    mode.0 = None;
+}
+
+/// Spawn a new ghost whenever the selected tower changes
+pub fn spawn_ghost_tower(
+    mut commands: Commands,
+    mode: Res<PlacementMode>,
+    ghost_query: Query<Entity, With<GhostTowerMarker>>,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+) {
+    if !mode.is_changed() {
+        return;
+    }
+
+    ghost_query.for_each(|ghost| commands.entity(ghost).despawn_recursive());
+
+    if let (Some(tower_type), Ok(world)) = (mode.0, world_query.get_single()) {
+        commands.entity(world).add_children(|parent| {
+            parent
+                .spawn_bundle(SpatialBundle {
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(GhostTowerMarker)
+                .insert(Name::new("Ghost Tower"))
+                .insert(crate::RemoveOnGameplayExit)
+                .with_children(|parent| {
+                    tower_type.spawn_visuals(parent, 0.5, 0);
+                    // Traps have no range, but still need a ring to show where they can be built
+                    let range = f32::from(tower_type.base_stats().range).max(0.5);
+                    parent
+                        .spawn_bundle(GeometryBuilder::build_as(
+                            &shapes::Circle {
+                                radius: range * TILE_SIZE,
+                                center: Vec2::ZERO,
+                            },
+                            DrawMode::Stroke(StrokeMode::new(Color::WHITE, 1.)),
+                            Transform::from_xyz(0., 0., 2.),
+                        ))
+                        .insert(GhostRangeMarker);
+                });
+        });
+    }
+}
+
+/// Move the ghost to the hovered tile, and show if we can build there
+pub fn move_ghost_tower(
+    hovered: Res<crate::mouse_location::MouseGridPos>,
+    mode: Res<PlacementMode>,
+    mut ghost_query: Query<(&mut Transform, &mut Visibility), With<GhostTowerMarker>>,
+    mut range_query: Query<&mut DrawMode, With<GhostRangeMarker>>,
+    towers: Query<&GridPosition, With<TowerMarker>>,
+    assets: Res<crate::assets::MiscAssets>,
+    asset_store: Res<Assets<bevy_ecs_ldtk::LdtkAsset>>,
+    current_level: Res<bevy_ecs_ldtk::LevelSelection>,
+) {
+    let tower_type = match mode.0 {
+        Some(tower_type) => tower_type,
+        None => return,
+    };
+    let tilemap = get_tilemap(&assets, &asset_store, &current_level);
+
+    for (mut trans, mut visibility) in ghost_query.iter_mut() {
+        visibility.is_visible = hovered.0.is_some();
+
+        if let Some(position) = hovered.0 {
+            trans.translation = position.to_world().extend(20.);
+
+            let color = if can_place_at(tower_type, position, tilemap, &towers) {
+                Color::WHITE
+            } else {
+                Color::RED
+            };
+            for mut draw_mode in range_query.iter_mut() {
+                *draw_mode = DrawMode::Stroke(StrokeMode::new(color, 1.));
+            }
+        }
+    }
+}
+
+/// Build the selected tower on the hovered tile when the player clicks
+pub fn place_tower(
+    mut commands: Commands,
+    mouse: Res<Input<MouseButton>>,
+    hovered: Res<crate::mouse_location::MouseGridPos>,
+    mut mode: ResMut<PlacementMode>,
+    mut gold: ResMut<PlayerGold>,
+    state: Res<CurrentState<TurnState>>,
+    towers: Query<&GridPosition, With<TowerMarker>>,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+    assets: Res<crate::assets::MiscAssets>,
+    asset_store: Res<Assets<bevy_ecs_ldtk::LdtkAsset>>,
+    current_level: Res<bevy_ecs_ldtk::LevelSelection>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (tower_type, position) = match (mode.0, hovered.0) {
+        (Some(tower_type), Some(position)) => (tower_type, position),
+        _ => return,
+    };
+
+    let tilemap = get_tilemap(&assets, &asset_store, &current_level);
+    if !can_buy(tower_type, state.0, &gold) || !can_place_at(tower_type, position, tilemap, &towers)
+    {
+        return;
+    }
+
+    gold.0 -= tower_type.cost();
+    mode.0 = None;
+
+    let world = world_query.single();
+    commands.entity(world).add_children(|parent| {
+        let mut tower = parent.spawn_bundle(TowerBundle::new(tower_type, position));
+        tower.with_children(|parent| tower_type.spawn_visuals(parent, 1., 0));
+        if let Some(trap) = tower_type.trap() {
+            tower.insert(TrapCharges(trap.charges));
+        }
+        if let Some(max_health) = tower_type.max_health() {
+            tower
+                .insert(TowerHealth(max_health))
+                .with_children(|parent| spawn_tower_healthbar(parent, max_health));
+        }
+    });
+}
@@ -0,0 +1,228 @@
+//! Shop in the bottom ui bar used to buy towers
+
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use iyes_loopless::prelude::*;
+
+use super::tower_placement::{can_buy, PlacementMode};
+use super::tower_types::TowerType;
+use crate::ldtk_loader::TileType;
+use crate::player::PlayerGold;
+use crate::ui::BottomBarMarker;
+use crate::TurnState;
+
+/// Color of a shop button the player can use
+const AVAILABLE_COLOR: Color = Color::rgb(0.4, 0.4, 0.4);
+/// Color of a shop button the mouse is over
+const HOVERED_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
+/// Color of the shop button for the tower we are placing
+const SELECTED_COLOR: Color = Color::rgb(0.6, 0.55, 0.3);
+/// Color of a shop button for a tower we cant buy right now
+const UNAVAILABLE_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+
+/// A button in the shop for buying a tower
+#[derive(Debug, Component, Clone, Copy)]
+pub struct ShopButton(pub TowerType);
+
+/// Marker for the tooltip shown above a shop button while it is hovered
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct ShopTooltipMarker;
+
+/// Add the shop to the bottom bar once it exists
+pub fn create_shop(
+    mut commands: Commands,
+    bar_query: Query<Entity, Added<BottomBarMarker>>,
+    assets: Res<crate::assets::MiscAssets>,
+) {
+    for bar in bar_query.iter() {
+        let text_style = TextStyle {
+            font: assets.font.clone_weak(),
+            font_size: 14.,
+            color: Color::WHITE,
+        };
+
+        commands.entity(bar).add_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        margin: UiRect {
+                            left: Val::Px(16.),
+                            ..default()
+                        },
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .insert(Name::new("Tower Shop"))
+                .with_children(|parent| {
+                    for tower_type in TowerType::ALL {
+                        spawn_shop_button(parent, tower_type, &text_style);
+                    }
+                });
+        });
+    }
+}
+
+/// Create the button used to buy a single tower type
+fn spawn_shop_button(parent: &mut ChildBuilder, tower_type: TowerType, text_style: &TextStyle) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(88.), Val::Px(96.)),
+                margin: UiRect::all(Val::Px(4.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceEvenly,
+                ..default()
+            },
+            color: AVAILABLE_COLOR.into(),
+            ..default()
+        })
+        .insert(ShopButton(tower_type))
+        .with_children(|parent| {
+            // Icon
+            parent.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Px(24.), Val::Px(24.)),
+                    ..default()
+                },
+                color: tower_type.color().into(),
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            });
+            parent.spawn_bundle(TextBundle::from_section(
+                tower_type.name(),
+                text_style.clone(),
+            ));
+            parent.spawn_bundle(TextBundle::from_section(
+                format!("{} gold", tower_type.cost()),
+                text_style.clone(),
+            ));
+            parent.spawn_bundle(TextBundle::from_section(
+                format!("[{}]", tower_type.hotkey_label()),
+                text_style.clone(),
+            ));
+
+            spawn_tooltip(parent, tower_type, text_style);
+        });
+}
+
+/// Create the hidden tooltip explaining a tower, including where it can be built
+fn spawn_tooltip(parent: &mut ChildBuilder, tower_type: TowerType, text_style: &TextStyle) {
+    let stats = tower_type.base_stats();
+    let tiles: Vec<_> = tower_type
+        .allowed_tiles()
+        .iter()
+        .copied()
+        .map(TileType::name)
+        .collect();
+    let mut info = if tower_type.detects_stealth() {
+        format!(
+            "{}\nRange: {}\nBuilt on: {}\nReveals stealthy enemies in range",
+            tower_type.name(),
+            stats.range,
+            tiles.join(", "),
+        )
+    } else {
+        format!(
+            "{}\nRange: {}  Damage: {} {}  Cooldown: {}\nBuilt on: {}",
+            tower_type.name(),
+            stats.range,
+            stats.damage,
+            tower_type
+                .damage_type()
+                .map_or("", |damage_type| damage_type.name()),
+            stats.cooldown,
+            tiles.join(", "),
+        )
+    };
+    if let Some(special) = stats.special {
+        info.push('\n');
+        info.push_str(&special.describe());
+    }
+    if let Some(effect) = stats.effect {
+        info.push('\n');
+        info.push_str(&effect.describe());
+    }
+    if tower_type.anti_air() {
+        info.push_str("\nHits flying enemies");
+    }
+
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(0.),
+                    bottom: Val::Px(100.),
+                    ..default()
+                },
+                padding: UiRect::all(Val::Px(4.)),
+                ..default()
+            },
+            color: Color::rgba(0., 0., 0., 0.8).into(),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .insert(ShopTooltipMarker)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(info, text_style.clone()));
+        });
+}
+
+/// Start placing a tower when its button is clicked
+pub fn shop_button_clicked(
+    query: Query<(&Interaction, &ShopButton), Changed<Interaction>>,
+    state: Res<CurrentState<TurnState>>,
+    gold: Res<PlayerGold>,
+    mut mode: ResMut<PlacementMode>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction == Interaction::Clicked && can_buy(button.0, state.0, &gold) {
+            mode.0 = Some(button.0);
+        }
+    }
+}
+
+/// Grey out towers we cant buy, and highlight the one we are placing
+pub fn update_shop_buttons(
+    mut query: Query<(&Interaction, &ShopButton, &mut UiColor)>,
+    state: Res<CurrentState<TurnState>>,
+    gold: Res<PlayerGold>,
+    mode: Res<PlacementMode>,
+) {
+    for (interaction, button, mut color) in query.iter_mut() {
+        let new_color = if !can_buy(button.0, state.0, &gold) {
+            UNAVAILABLE_COLOR
+        } else if mode.0 == Some(button.0) {
+            SELECTED_COLOR
+        } else if *interaction == Interaction::None {
+            AVAILABLE_COLOR
+        } else {
+            HOVERED_COLOR
+        };
+
+        color.0 = new_color;
+    }
+}
+
+/// Show the tooltip of the shop button the mouse is over
+pub fn show_shop_tooltips(
+    query: Query<(&Interaction, &Children), (With<ShopButton>, Changed<Interaction>)>,
+    mut tooltip_query: Query<&mut Style, With<ShopTooltipMarker>>,
+) {
+    for (interaction, children) in query.iter() {
+        for child in children.iter() {
+            if let Ok(mut style) = tooltip_query.get_mut(*child) {
+                style.display = if *interaction == Interaction::None {
+                    Display::None
+                } else {
+                    Display::Flex
+                };
+            }
+        }
+    }
+}
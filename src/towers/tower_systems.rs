@@ -0,0 +1,12 @@
+//! Tower systems control tower behaviour!
+
+use bevy::prelude::*;
+
+use super::tower_components::{TowerCooldown, TowerMarker};
+
+/// Count down the cooldown of every tower
+pub fn reduce_cooldowns(mut query: Query<&mut TowerCooldown, With<TowerMarker>>) {
+    for mut cooldown in query.iter_mut() {
+        cooldown.0 = cooldown.0.saturating_sub(1);
+    }
+}
@@ -0,0 +1,166 @@
+//! Decide which enemies towers attack
+
+use std::cmp::Reverse;
+
+use bevy::prelude::*;
+
+use super::tower_attacks::{AttackQueue, QueuedAttack};
+use super::tower_components::{TowerCooldown, TowerMarker, TrapCharges};
+use super::tower_health::Stunned;
+use super::tower_types::{TowerStats, TowerType};
+use crate::enemies::{
+    Burrowed, EnemyHealth, EnemyMarker, EnemyPath, EnemyRegistry, EnemyType, Flying, Footprint,
+    Hidden,
+};
+use crate::grid_position::GridPosition;
+use crate::player::AttackMode;
+
+/// Which enemy should a tower prefer to attack?
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TargetingMode {
+    /// Enemy furthest along its path
+    #[default]
+    First,
+    /// Enemy least far along its path
+    Last,
+    /// Enemy with the most health
+    Strongest,
+    /// Enemy with the least health
+    Weakest,
+    /// Enemy closest to the tower
+    Closest,
+    /// Enemies of this type, then the enemy furthest along its path
+    Type(EnemyType),
+}
+
+impl TargetingMode {
+    /// Name shown to the player
+    pub fn name(self, registry: &EnemyRegistry) -> String {
+        match self {
+            Self::First => "First".to_owned(),
+            Self::Last => "Last".to_owned(),
+            Self::Strongest => "Strongest".to_owned(),
+            Self::Weakest => "Weakest".to_owned(),
+            Self::Closest => "Closest".to_owned(),
+            Self::Type(enemy_type) => registry.get(enemy_type).map_or_else(
+                || "Removed enemy".to_owned(),
+                |definition| definition.name.clone(),
+            ),
+        }
+    }
+
+    /// The mode that comes after this one when the player cycles through them
+    pub fn next(self, registry: &EnemyRegistry) -> Self {
+        match self {
+            Self::First => Self::Last,
+            Self::Last => Self::Strongest,
+            Self::Strongest => Self::Weakest,
+            Self::Weakest => Self::Closest,
+            Self::Closest => registry.types().next().map_or(Self::First, Self::Type),
+            Self::Type(enemy_type) => registry
+                .types()
+                .find(|other| other.0 > enemy_type.0)
+                .map_or(Self::First, Self::Type),
+        }
+    }
+}
+
+/// Everything targeting needs to know about an enemy
+pub type EnemyInfo<'a> = (
+    Entity,
+    &'a GridPosition,
+    &'a EnemyPath,
+    &'a EnemyHealth,
+    &'a EnemyType,
+    &'a Footprint,
+);
+
+/// Can a tower of this type attack this enemy? Only anti air towers can hit flying enemies
+pub fn can_target(tower_type: TowerType, flying: Option<&Flying>) -> bool {
+    flying.is_none() || tower_type.anti_air()
+}
+
+/// Order enemies in range of a tower from most to least preferred
+pub fn pick_targets<'a>(
+    mode: TargetingMode,
+    tower_pos: GridPosition,
+    range: u8,
+    enemies: impl Iterator<Item = EnemyInfo<'a>>,
+) -> Vec<Entity> {
+    // Big enemies are in range when any tile they cover is
+    let mut in_range: Vec<_> = enemies
+        .map(|enemy| {
+            let (_, enemy_pos, _, _, _, footprint) = enemy;
+            (enemy, footprint.distance(*enemy_pos, tower_pos))
+        })
+        .filter(|(_, distance)| *distance <= f32::from(range))
+        .collect();
+
+    // Sort by how far along the path the enemy is first, so ties are broken in a sensible way
+    in_range.sort_by_key(|((_, pos, path, ..), _)| path.remaining_distance(pos.0));
+    match mode {
+        TargetingMode::First => {}
+        TargetingMode::Last => in_range.reverse(),
+        TargetingMode::Strongest => {
+            in_range.sort_by_key(|((_, _, _, health, ..), _)| Reverse(health.0));
+        }
+        TargetingMode::Weakest => in_range.sort_by_key(|((_, _, _, health, ..), _)| health.0),
+        TargetingMode::Closest => in_range.sort_by(|(_, a), (_, b)| a.total_cmp(b)),
+        TargetingMode::Type(wanted) => {
+            in_range.sort_by_key(|((_, _, _, _, enemy_type, _), _)| **enemy_type != wanted);
+        }
+    }
+
+    in_range.into_iter().map(|((enemy, ..), _)| enemy).collect()
+}
+
+/// Queue attacks for every tower that is ready when towers attack on their own
+pub fn choose_targets(
+    attack_mode: Res<AttackMode>,
+    mut queue: ResMut<AttackQueue>,
+    towers: Query<
+        (
+            Entity,
+            &TowerType,
+            &TowerStats,
+            &GridPosition,
+            &TowerCooldown,
+            &TargetingMode,
+        ),
+        // Traps trigger on their own when enemies step on them, stunned towers skip this attack
+        (With<TowerMarker>, Without<TrapCharges>, Without<Stunned>),
+    >,
+    enemies: Query<
+        (
+            (
+                Entity,
+                &GridPosition,
+                &EnemyPath,
+                &EnemyHealth,
+                &EnemyType,
+                &Footprint,
+            ),
+            Option<&Flying>,
+        ),
+        (With<EnemyMarker>, Without<Hidden>, Without<Burrowed>),
+    >,
+) {
+    if *attack_mode != AttackMode::Automatic {
+        return;
+    }
+
+    for (tower, tower_type, stats, tower_pos, cooldown, mode) in towers.iter() {
+        if cooldown.0 > 0 || !tower_type.attacks() {
+            continue;
+        }
+
+        let targetable = enemies
+            .iter()
+            .filter(|(_, flying)| can_target(*tower_type, *flying))
+            .map(|(enemy, _)| enemy);
+        let targets = pick_targets(*mode, *tower_pos, stats.range, targetable);
+        for target in targets.into_iter().take(stats.shots()) {
+            queue.0.push(QueuedAttack { tower, target });
+        }
+    }
+}
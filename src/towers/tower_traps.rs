@@ -0,0 +1,144 @@
+//! Traps built on the path that trigger when enemies step on them
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use super::tower_attacks::spawn_impact_flash;
+use super::tower_components::TrapCharges;
+use super::tower_panel::{SelectedTower, UpgradeButton};
+use super::tower_types::{TowerStats, TowerType, TrapEffect};
+use crate::enemies::{
+    ApplyStatus, Burrowed, DamageEnemy, EnemyMarker, EnemyStepped, Flying, Footprint, StatusEffect,
+    StatusKind,
+};
+use crate::grid_position::GridPosition;
+use crate::player::PlayerGold;
+use crate::{TurnPart, TurnState};
+
+/// Trigger traps when enemies move onto their tile
+///
+/// This uses [`EnemyStepped`] so traps on tiles enemies walk past in a single move still trigger.
+/// Traps without a re-arm cost are removed once they run out of charges
+pub fn trigger_traps(
+    mut commands: Commands,
+    mut stepped_events: EventReader<EnemyStepped>,
+    // Flying enemies dont touch the ground and burrowed ones are below it, so traps cant hurt them
+    enemies: Query<
+        (Entity, &GridPosition, &Footprint),
+        (With<EnemyMarker>, Without<Flying>, Without<Burrowed>),
+    >,
+    mut traps: Query<(
+        Entity,
+        &TowerType,
+        &TowerStats,
+        &GridPosition,
+        &Transform,
+        &mut TrapCharges,
+    )>,
+    world_query: Query<Entity, With<crate::ldtk_loader::WorldMarker>>,
+    mut damage_events: EventWriter<DamageEnemy>,
+    mut status_events: EventWriter<ApplyStatus>,
+) {
+    for event in stepped_events.iter() {
+        let enemy = event.enemy;
+        if enemies.get(enemy).is_err() {
+            continue;
+        }
+        for (trap_entity, tower_type, stats, trap_pos, trans, mut charges) in traps.iter_mut() {
+            if *trap_pos != event.position || charges.0 == 0 {
+                continue;
+            }
+            let trap = match tower_type.trap() {
+                Some(trap) => trap,
+                None => continue,
+            };
+            let damage_type = match tower_type.damage_type() {
+                Some(damage_type) => damage_type,
+                None => continue,
+            };
+
+            let mut hit = |target, amount| {
+                damage_events.send(DamageEnemy {
+                    target,
+                    amount,
+                    damage_type,
+                    source: Some(trap_entity),
+                });
+                if let Some(effect) = stats.effect {
+                    status_events.send(ApplyStatus { target, effect });
+                }
+            };
+            match trap.effect {
+                TrapEffect::Spikes => hit(enemy, stats.damage),
+                TrapEffect::Net(moves) => status_events.send(ApplyStatus {
+                    target: enemy,
+                    effect: StatusEffect {
+                        kind: StatusKind::Freeze,
+                        strength: 0,
+                        turns: moves,
+                    },
+                }),
+                TrapEffect::Explode(splash) => {
+                    for (other, other_pos, footprint) in enemies.iter() {
+                        let amount = splash.damage_at(
+                            stats.damage,
+                            footprint.tiles_between(*other_pos, *trap_pos),
+                        );
+                        if amount > 0 {
+                            hit(other, amount);
+                        }
+                    }
+                }
+            }
+
+            commands
+                .entity(world_query.single())
+                .add_children(|parent| {
+                    spawn_impact_flash(
+                        parent,
+                        trans.translation.truncate().extend(30.),
+                        tower_type.color(),
+                    );
+                });
+
+            charges.0 -= 1;
+            if charges.0 == 0 && trap.rearm_cost.is_none() {
+                commands.entity(trap_entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Re-arm the selected trap when the upgrade button is clicked
+///
+/// Traps cant be upgraded, so they use the upgrade button for this instead
+pub fn rearm_trap(
+    button_query: Query<&Interaction, (With<UpgradeButton>, Changed<Interaction>)>,
+    selected: Res<SelectedTower>,
+    state: Res<CurrentState<TurnState>>,
+    mut gold: ResMut<PlayerGold>,
+    mut traps: Query<(&TowerType, &mut TrapCharges)>,
+) {
+    let clicked = button_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !clicked || state.0 != TurnState::InTurn(TurnPart::PlayerAction) {
+        return;
+    }
+
+    let (tower_type, mut charges) = match selected.0.and_then(|tower| traps.get_mut(tower).ok()) {
+        Some(trap) => trap,
+        None => return,
+    };
+    let trap = match tower_type.trap() {
+        Some(trap) => trap,
+        None => return,
+    };
+    match trap.rearm_cost {
+        Some(cost) if charges.0 < trap.charges && gold.0 >= cost => {
+            gold.0 -= cost;
+            charges.0 = trap.charges;
+        }
+        _ => {}
+    }
+}
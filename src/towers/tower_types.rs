@@ -0,0 +1,668 @@
+//! Define settings and beheaviour of different tower types
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::{entity::ShapeBundle, prelude::*};
+
+use crate::enemies::{DamageType, StatusEffect, StatusKind};
+use crate::ldtk_loader::TileType;
+
+/// Stats of a tower at a certain level
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TowerStats {
+    /// How many tiles away can this tower hit enemies?
+    pub range: u8,
+    /// How much damage does a single attack do?
+    pub damage: u8,
+    /// How many turns does the tower need to wait between attacks?
+    pub cooldown: u8,
+    /// Extra effect unlocked by upgrading
+    pub special: Option<TowerSpecial>,
+    /// Status effect applied to every enemy hit
+    pub effect: Option<StatusEffect>,
+}
+
+impl TowerStats {
+    /// How many enemies can the tower attack each turn?
+    pub fn shots(&self) -> usize {
+        match self.special {
+            Some(TowerSpecial::MultiShot(count)) => usize::from(count),
+            Some(TowerSpecial::Splash(_)) | None => 1,
+        }
+    }
+}
+
+/// Special effects towers can get from upgrades
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TowerSpecial {
+    /// Attack this many different enemies at once
+    MultiShot(u8),
+    /// Damage every enemy around the target
+    Splash(Splash),
+}
+
+impl TowerSpecial {
+    /// Short description shown to the player
+    pub fn describe(self) -> String {
+        match self {
+            Self::MultiShot(count) => format!("Hits {count} enemies"),
+            Self::Splash(Splash { radius: 0, .. }) => "Hits every enemy on a tile".to_owned(),
+            Self::Splash(Splash { radius, falloff }) => {
+                format!("Splash {radius} tiles, -{falloff}% per tile")
+            }
+        }
+    }
+}
+
+/// Area hit by a splash attack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Splash {
+    /// How many tiles away from the target tile are hit, 0 only hits the target tile
+    pub radius: u8,
+    /// Percent of the damage lost for each tile away from the target tile
+    pub falloff: u8,
+}
+
+impl Splash {
+    /// Damage done to enemies `distance` tiles away from the target tile
+    pub fn damage_at(self, damage: u8, distance: u32) -> u8 {
+        if distance > u32::from(self.radius) {
+            return 0;
+        }
+        let percent = 100_u32.saturating_sub(u32::from(self.falloff) * distance);
+        (u32::from(damage) * percent / 100) as u8
+    }
+}
+
+/// What a trap does to the enemy that steps on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapEffect {
+    /// Damage the enemy
+    Spikes,
+    /// Make the enemy skip this many moves
+    Net(u8),
+    /// Damage every enemy in a area
+    Explode(Splash),
+}
+
+/// Settings of a trap, traps are built on the path and trigger when enemies step on them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trap {
+    /// What happens when the trap triggers
+    pub effect: TrapEffect,
+    /// How many times the trap triggers before it needs to be re-armed
+    pub charges: u8,
+    /// Gold needed to re-arm the trap, traps without one are consumed when out of charges
+    pub rearm_cost: Option<u32>,
+}
+
+impl Trap {
+    /// Short description shown to the player
+    pub fn describe(self) -> String {
+        match self.effect {
+            TrapEffect::Spikes => "Damages the enemy stepping on it".to_owned(),
+            TrapEffect::Net(moves) => format!("Enemy skips its next {moves} moves"),
+            TrapEffect::Explode(Splash { radius, .. }) => {
+                format!("Explodes, hitting enemies {radius} tiles away")
+            }
+        }
+    }
+}
+
+/// A level a tower can be upgraded to
+#[derive(Debug, Clone, Copy)]
+pub struct TowerUpgrade {
+    /// Gold needed to reach this level
+    pub cost: u32,
+    /// Stats of the tower at this level
+    pub stats: TowerStats,
+}
+
+/// Tower Types
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TowerType {
+    /// Cheap tower that shoots every turn
+    Archer,
+    /// Slow but hard hitting tower
+    Cannon,
+    /// Lobs shells that hit every enemy in a area
+    Mortar,
+    /// Cannon on a ship, can only be built on water
+    NavalCannon,
+    /// Trap damaging enemies that step on it
+    Spike,
+    /// Trap holding enemies in place
+    Net,
+    /// Single use trap that blows up
+    Mine,
+    /// Does not attack, but reveals stealthy enemies around it
+    Watchtower,
+}
+
+impl TowerType {
+    /// Every tower the player can buy, in the order they are shown in the shop
+    pub const ALL: [Self; 8] = [
+        Self::Archer,
+        Self::Cannon,
+        Self::Mortar,
+        Self::NavalCannon,
+        Self::Spike,
+        Self::Net,
+        Self::Mine,
+        Self::Watchtower,
+    ];
+
+    /// Name shown to the player
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Archer => "Archer",
+            Self::Cannon => "Cannon",
+            Self::Mortar => "Mortar",
+            Self::NavalCannon => "Naval Cannon",
+            Self::Spike => "Spikes",
+            Self::Net => "Net",
+            Self::Mine => "Mine",
+            Self::Watchtower => "Watchtower",
+        }
+    }
+
+    /// Every level this tower can reach, the first level is the tower as it is built
+    pub fn levels(self) -> &'static [TowerUpgrade] {
+        match self {
+            Self::Archer => &[
+                TowerUpgrade {
+                    cost: 5,
+                    stats: TowerStats {
+                        range: 2,
+                        damage: 1,
+                        cooldown: 0,
+                        special: None,
+                        effect: None,
+                    },
+                },
+                TowerUpgrade {
+                    cost: 5,
+                    stats: TowerStats {
+                        range: 3,
+                        damage: 1,
+                        cooldown: 0,
+                        special: None,
+                        effect: None,
+                    },
+                },
+                TowerUpgrade {
+                    cost: 10,
+                    stats: TowerStats {
+                        range: 3,
+                        damage: 1,
+                        cooldown: 0,
+                        special: Some(TowerSpecial::MultiShot(2)),
+                        effect: None,
+                    },
+                },
+            ],
+            Self::Cannon => &[
+                TowerUpgrade {
+                    cost: 10,
+                    stats: TowerStats {
+                        range: 3,
+                        damage: 3,
+                        cooldown: 2,
+                        special: None,
+                        effect: None,
+                    },
+                },
+                TowerUpgrade {
+                    cost: 8,
+                    stats: TowerStats {
+                        range: 3,
+                        damage: 5,
+                        cooldown: 2,
+                        special: None,
+                        effect: None,
+                    },
+                },
+                TowerUpgrade {
+                    cost: 12,
+                    stats: TowerStats {
+                        range: 4,
+                        damage: 5,
+                        cooldown: 1,
+                        special: None,
+                        effect: Some(StatusEffect {
+                            kind: StatusKind::Burn,
+                            strength: 1,
+                            turns: 2,
+                        }),
+                    },
+                },
+            ],
+            Self::Mortar => &[
+                TowerUpgrade {
+                    cost: 12,
+                    stats: TowerStats {
+                        range: 4,
+                        damage: 2,
+                        cooldown: 1,
+                        special: Some(TowerSpecial::Splash(Splash {
+                            radius: 0,
+                            falloff: 0,
+                        })),
+                        effect: None,
+                    },
+                },
+                TowerUpgrade {
+                    cost: 10,
+                    stats: TowerStats {
+                        range: 4,
+                        damage: 2,
+                        cooldown: 1,
+                        special: Some(TowerSpecial::Splash(Splash {
+                            radius: 1,
+                            falloff: 50,
+                        })),
+                        effect: None,
+                    },
+                },
+                TowerUpgrade {
+                    cost: 14,
+                    stats: TowerStats {
+                        range: 5,
+                        damage: 3,
+                        cooldown: 1,
+                        special: Some(TowerSpecial::Splash(Splash {
+                            radius: 1,
+                            falloff: 25,
+                        })),
+                        effect: Some(StatusEffect {
+                            kind: StatusKind::Slow,
+                            strength: 0,
+                            turns: 2,
+                        }),
+                    },
+                },
+            ],
+            Self::NavalCannon => &[
+                TowerUpgrade {
+                    cost: 8,
+                    stats: TowerStats {
+                        range: 3,
+                        damage: 3,
+                        cooldown: 1,
+                        special: None,
+                        effect: None,
+                    },
+                },
+                TowerUpgrade {
+                    cost: 8,
+                    stats: TowerStats {
+                        range: 4,
+                        damage: 3,
+                        cooldown: 1,
+                        special: None,
+                        effect: Some(StatusEffect {
+                            kind: StatusKind::Weaken,
+                            strength: 1,
+                            turns: 2,
+                        }),
+                    },
+                },
+                TowerUpgrade {
+                    cost: 12,
+                    stats: TowerStats {
+                        range: 4,
+                        damage: 3,
+                        cooldown: 1,
+                        special: Some(TowerSpecial::MultiShot(2)),
+                        effect: Some(StatusEffect {
+                            kind: StatusKind::Weaken,
+                            strength: 1,
+                            turns: 2,
+                        }),
+                    },
+                },
+            ],
+            // Traps only use their damage and effect, and are never upgraded
+            Self::Spike => &[TowerUpgrade {
+                cost: 4,
+                stats: TowerStats {
+                    range: 0,
+                    damage: 2,
+                    cooldown: 0,
+                    special: None,
+                    effect: Some(StatusEffect {
+                        kind: StatusKind::Poison,
+                        strength: 1,
+                        turns: 3,
+                    }),
+                },
+            }],
+            Self::Net => &[TowerUpgrade {
+                cost: 5,
+                stats: TowerStats {
+                    range: 0,
+                    damage: 0,
+                    cooldown: 0,
+                    special: None,
+                    effect: None,
+                },
+            }],
+            Self::Mine => &[TowerUpgrade {
+                cost: 6,
+                stats: TowerStats {
+                    range: 0,
+                    damage: 4,
+                    cooldown: 0,
+                    special: None,
+                    effect: None,
+                },
+            }],
+            // Watchtowers only use their range
+            Self::Watchtower => &[
+                TowerUpgrade {
+                    cost: 6,
+                    stats: TowerStats {
+                        range: 2,
+                        damage: 0,
+                        cooldown: 0,
+                        special: None,
+                        effect: None,
+                    },
+                },
+                TowerUpgrade {
+                    cost: 6,
+                    stats: TowerStats {
+                        range: 3,
+                        damage: 0,
+                        cooldown: 0,
+                        special: None,
+                        effect: None,
+                    },
+                },
+                TowerUpgrade {
+                    cost: 10,
+                    stats: TowerStats {
+                        range: 4,
+                        damage: 0,
+                        cooldown: 0,
+                        special: None,
+                        effect: None,
+                    },
+                },
+            ],
+        }
+    }
+
+    /// Trap settings, if this is a trap
+    pub fn trap(self) -> Option<Trap> {
+        match self {
+            Self::Archer | Self::Cannon | Self::Mortar | Self::NavalCannon | Self::Watchtower => {
+                None
+            }
+            Self::Spike => Some(Trap {
+                effect: TrapEffect::Spikes,
+                charges: 3,
+                rearm_cost: Some(2),
+            }),
+            Self::Net => Some(Trap {
+                effect: TrapEffect::Net(1),
+                charges: 2,
+                rearm_cost: Some(3),
+            }),
+            Self::Mine => Some(Trap {
+                effect: TrapEffect::Explode(Splash {
+                    radius: 1,
+                    falloff: 50,
+                }),
+                charges: 1,
+                rearm_cost: None,
+            }),
+        }
+    }
+
+    /// How much health the tower has, enemies can't attack traps so they have none
+    pub fn max_health(self) -> Option<u8> {
+        match self {
+            Self::Archer => Some(5),
+            Self::Cannon | Self::NavalCannon => Some(8),
+            Self::Mortar => Some(6),
+            Self::Watchtower => Some(4),
+            Self::Spike | Self::Net | Self::Mine => None,
+        }
+    }
+
+    /// Does this tower shoot at enemies? Traps trigger on their own instead
+    pub fn attacks(self) -> bool {
+        self.trap().is_none() && !self.detects_stealth()
+    }
+
+    /// Does this tower reveal stealthy enemies in its range?
+    pub fn detects_stealth(self) -> bool {
+        matches!(self, Self::Watchtower)
+    }
+
+    /// Can this tower hit flying enemies?
+    pub fn anti_air(self) -> bool {
+        matches!(self, Self::Archer)
+    }
+
+    /// What kind of damage does this tower do? `None` for towers that never attack
+    pub fn damage_type(self) -> Option<DamageType> {
+        match self {
+            Self::Archer | Self::Cannon | Self::Spike | Self::Net => Some(DamageType::Physical),
+            Self::Mortar | Self::Mine => Some(DamageType::Fire),
+            Self::NavalCannon => Some(DamageType::Ice),
+            Self::Watchtower => None,
+        }
+    }
+
+    /// Tiles this tower can be built on
+    pub fn allowed_tiles(self) -> &'static [TileType] {
+        match self {
+            Self::Archer | Self::Cannon | Self::Mortar | Self::Watchtower => &[TileType::Grass],
+            Self::NavalCannon => &[TileType::Water],
+            Self::Spike | Self::Net | Self::Mine => &[TileType::Path],
+        }
+    }
+
+    /// Can this tower be built on this tile type?
+    pub fn can_build_on(self, tile_type: TileType) -> bool {
+        self.allowed_tiles().contains(&tile_type)
+    }
+
+    /// How much gold does it cost to build this tower?
+    pub fn cost(self) -> u32 {
+        self.levels()[0].cost
+    }
+
+    /// Stats of the tower as it is built
+    pub fn base_stats(self) -> TowerStats {
+        self.levels()[0].stats
+    }
+
+    /// The upgrade that takes a tower from `level` to the next one, if there is one
+    pub fn next_upgrade(self, level: usize) -> Option<&'static TowerUpgrade> {
+        self.levels().get(level + 1)
+    }
+
+    /// Key used to select this tower in the shop
+    pub fn hotkey(self) -> KeyCode {
+        match self {
+            Self::Archer => KeyCode::Key1,
+            Self::Cannon => KeyCode::Key2,
+            Self::Mortar => KeyCode::Key3,
+            Self::NavalCannon => KeyCode::Key4,
+            Self::Spike => KeyCode::Key5,
+            Self::Net => KeyCode::Key6,
+            Self::Mine => KeyCode::Key7,
+            Self::Watchtower => KeyCode::Key8,
+        }
+    }
+
+    /// Label of the hotkey shown in the shop
+    pub fn hotkey_label(self) -> &'static str {
+        match self {
+            Self::Archer => "1",
+            Self::Cannon => "2",
+            Self::Mortar => "3",
+            Self::NavalCannon => "4",
+            Self::Spike => "5",
+            Self::Net => "6",
+            Self::Mine => "7",
+            Self::Watchtower => "8",
+        }
+    }
+
+    /// Main color of the tower
+    pub fn color(self) -> Color {
+        match self {
+            Self::Archer => Color::rgb(0.2, 0.6, 0.2),
+            Self::Cannon => Color::rgb(0.5, 0.5, 0.55),
+            Self::Mortar => Color::rgb(0.7, 0.4, 0.15),
+            Self::NavalCannon => Color::rgb(0.2, 0.3, 0.6),
+            Self::Spike => Color::rgb(0.75, 0.75, 0.75),
+            Self::Net => Color::rgb(0.8, 0.7, 0.45),
+            Self::Mine => Color::rgb(0.6, 0.1, 0.1),
+            Self::Watchtower => Color::rgb(0.9, 0.85, 0.4),
+        }
+    }
+
+    /// Create the shape drawn on top of the tower base
+    ///
+    /// `alpha` is used to draw see through towers, like the placement ghost
+    pub fn top_shape(self, alpha: f32) -> ShapeBundle {
+        let mut color = self.color();
+        color.set_a(alpha);
+        let fill = DrawMode::Outlined {
+            fill_mode: FillMode::color(color),
+            outline_mode: StrokeMode::new(Color::rgba(0., 0., 0., alpha), 1.),
+        };
+        let transform = Transform::from_xyz(0., 0., 1.);
+
+        match self {
+            Self::Archer => GeometryBuilder::build_as(
+                &shapes::RegularPolygon {
+                    sides: 3,
+                    feature: shapes::RegularPolygonFeature::Radius(5.),
+                    center: Vec2::ZERO,
+                },
+                fill,
+                transform,
+            ),
+            Self::Cannon => GeometryBuilder::build_as(
+                &shapes::Circle {
+                    radius: 4.,
+                    center: Vec2::ZERO,
+                },
+                fill,
+                transform,
+            ),
+            Self::Mortar => GeometryBuilder::build_as(
+                &shapes::RegularPolygon {
+                    sides: 4,
+                    feature: shapes::RegularPolygonFeature::Radius(5.),
+                    center: Vec2::ZERO,
+                },
+                fill,
+                transform,
+            ),
+            Self::NavalCannon => GeometryBuilder::build_as(
+                &shapes::RegularPolygon {
+                    sides: 6,
+                    feature: shapes::RegularPolygonFeature::Radius(5.),
+                    center: Vec2::ZERO,
+                },
+                fill,
+                transform,
+            ),
+            Self::Spike => GeometryBuilder::build_as(
+                &shapes::RegularPolygon {
+                    sides: 3,
+                    feature: shapes::RegularPolygonFeature::Radius(3.),
+                    center: Vec2::ZERO,
+                },
+                fill,
+                transform,
+            ),
+            Self::Net => GeometryBuilder::build_as(
+                &shapes::Rectangle {
+                    extents: Vec2::new(10., 10.),
+                    origin: RectangleOrigin::Center,
+                },
+                DrawMode::Stroke(StrokeMode::new(color, 1.)),
+                transform,
+            ),
+            Self::Mine => GeometryBuilder::build_as(
+                &shapes::Circle {
+                    radius: 3.,
+                    center: Vec2::ZERO,
+                },
+                fill,
+                transform,
+            ),
+            // A lens, looking out for hidden enemies
+            Self::Watchtower => GeometryBuilder::build_as(
+                &shapes::Circle {
+                    radius: 4.,
+                    center: Vec2::ZERO,
+                },
+                DrawMode::Stroke(StrokeMode::new(color, 1.5)),
+                transform,
+            ),
+        }
+    }
+
+    /// Spawn the shapes that make up this tower
+    ///
+    /// Upgraded towers get a brighter outline and a pip for each level above the first,
+    /// traps lie flat on the path so they have no base
+    pub fn spawn_visuals(self, parent: &mut ChildBuilder, alpha: f32, level: usize) {
+        if self.trap().is_some() {
+            parent
+                .spawn_bundle(self.top_shape(alpha))
+                .insert(TowerVisualMarker);
+            return;
+        }
+
+        let outline = match level {
+            0 => Color::rgba(0., 0., 0., alpha),
+            1 => Color::rgba(0.75, 0.75, 0.8, alpha),
+            _ => Color::rgba(1., 0.8, 0.2, alpha),
+        };
+
+        // Base
+        parent
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shapes::Rectangle {
+                    extents: Vec2::new(12., 12.),
+                    origin: RectangleOrigin::Center,
+                },
+                DrawMode::Outlined {
+                    fill_mode: FillMode::color(Color::rgba(0.35, 0.25, 0.2, alpha)),
+                    outline_mode: StrokeMode::new(outline, 1.),
+                },
+                Transform::default(),
+            ))
+            .insert(TowerVisualMarker);
+        parent
+            .spawn_bundle(self.top_shape(alpha))
+            .insert(TowerVisualMarker);
+
+        // Level pips
+        for pip in 0..level {
+            parent
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &shapes::Rectangle {
+                        extents: Vec2::new(2., 2.),
+                        origin: RectangleOrigin::Center,
+                    },
+                    DrawMode::Fill(FillMode::color(Color::rgba(1., 0.8, 0.2, alpha))),
+                    Transform::from_xyz(-3. + pip as f32 * 3., -4., 2.),
+                ))
+                .insert(TowerVisualMarker);
+        }
+    }
+}
+
+/// Marker for the shapes a tower is drawn with
+#[derive(Debug, Default, Component, Clone, Copy)]
+pub struct TowerVisualMarker;
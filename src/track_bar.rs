@@ -24,6 +24,9 @@ pub struct TrackbarSettings {
     /// Filled color
     pub filled_color: Color,
 
+    /// Color of the secondary amount
+    pub secondary_color: Color,
+
     /// Not Filled color
     pub background_color: Color,
 }
@@ -32,10 +35,20 @@ pub struct TrackbarSettings {
 #[derive(Debug, Component, Clone, Copy, Default)]
 pub struct TrackbarProgess(pub usize);
 
+/// A second amount shown right after the progress, like armor after health
+///
+/// Both share the total of the settings
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct TrackbarSecondary(pub usize);
+
 /// Marker for the filled part of the tracker
 #[derive(Debug, Component, Default, Clone, Copy)]
 struct FilledMarker;
 
+/// Marker for the part of the tracker filled by the secondary amount
+#[derive(Debug, Component, Default, Clone, Copy)]
+struct SecondaryMarker;
+
 /// All components used by a track bar
 #[derive(Bundle, Default)]
 pub struct TrackbarBundle {
@@ -43,6 +56,8 @@ pub struct TrackbarBundle {
     pub settings: TrackbarSettings,
     /// Progress
     pub progress: TrackbarProgess,
+    /// Secondary amount
+    pub secondary: TrackbarSecondary,
     /// Give us an position
     #[bundle]
     pub position: SpatialBundle,
@@ -70,20 +85,41 @@ impl TrackbarBundle {
                 Transform::from_xyz(-settings.width / 2., 0., 1.),
             ))
             .insert(FilledMarker);
+        parent
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shapes::Rectangle {
+                    extents: Vec2::new(1., 1.),
+                    origin: RectangleOrigin::TopLeft,
+                },
+                DrawMode::Fill(FillMode::color(settings.secondary_color)),
+                Transform::from_xyz(-settings.width / 2., 0., 1.),
+            ))
+            .insert(SecondaryMarker);
     }
 }
 
 /// Set progress amount
 fn set_progress_amopunt(
-    mut query: Query<(&mut Transform, &Parent), With<FilledMarker>>,
-    p_query: Query<(&TrackbarProgess, &TrackbarSettings)>,
+    mut query: Query<
+        (&mut Transform, &Parent, Option<&SecondaryMarker>),
+        Or<(With<FilledMarker>, With<SecondaryMarker>)>,
+    >,
+    p_query: Query<(&TrackbarProgess, &TrackbarSecondary, &TrackbarSettings)>,
 ) {
-    for (mut trans, parent) in query.iter_mut() {
-        let (progress, settings) = p_query.get(parent.get()).unwrap();
+    for (mut trans, parent, secondary_marker) in query.iter_mut() {
+        let (progress, secondary, settings) = p_query.get(parent.get()).unwrap();
 
         let procent_done = progress.0 as f32 / settings.total as f32;
         let width = settings.width * procent_done;
 
-        trans.scale.x = width;
+        if secondary_marker.is_some() {
+            // Start where the normal progress ends
+            trans.translation.x = -settings.width / 2. + width;
+            // Dont run past the end of the bar when the secondary amount grew past the total
+            let secondary = secondary.0.min(settings.total.saturating_sub(progress.0));
+            trans.scale.x = settings.width * secondary as f32 / settings.total as f32;
+        } else {
+            trans.scale.x = width;
+        }
     }
 }
@@ -4,6 +4,8 @@ use bevy::prelude::*;
 use bevy_mod_ui_texture_atlas_image::{AtlasImageBundle, UiAtlasImage};
 use iyes_loopless::prelude::*;
 
+use crate::enemies::{EndlessMode, EndlessRun, MovePreviewTurns, WavesCleared};
+use crate::player::{AttackMode, EndTurnRequested, PlayerGold, PlayerHealth};
 use crate::{TurnPart, TurnState};
 
 /// How much space should the ui have at the bottom of the screen?
@@ -16,13 +18,45 @@ impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.add_enter_system(crate::MainState::Playing, create_ui);
         app.add_system(set_turn_icon.run_in_state(crate::MainState::Playing));
+        app.add_system(update_stats_text.run_in_state(crate::MainState::Playing));
+        app.add_system(show_waves_cleared.run_in_state(crate::MainState::Playing));
+        app.add_system(end_turn_button.run_in_state(crate::MainState::Playing));
+        app.add_system(attack_mode_button.run_in_state(crate::MainState::Playing));
     }
 }
 
+/// Mark the bar at the bottom of the screen, other plugins add their widgets to it
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct BottomBarMarker;
+
 /// Mark an entity as the turn icon in the ui
 #[derive(Component, Default)]
 struct TurnIconMarker;
 
+/// Mark the text showing the players gold and health
+#[derive(Component, Default)]
+struct StatsTextMarker;
+
+/// Mark the text telling the player they beat every wave
+#[derive(Component, Default)]
+struct WavesClearedTextMarker;
+
+/// Mark the button used to end the player action part of the turn
+#[derive(Component, Default)]
+struct EndTurnButtonMarker;
+
+/// Mark the text on the end turn button
+#[derive(Component, Default)]
+struct EndTurnTextMarker;
+
+/// Mark the button switching between automatic and manual attacks
+#[derive(Component, Default)]
+struct AttackModeButtonMarker;
+
+/// Mark the text on the attack mode button
+#[derive(Component, Default)]
+struct AttackModeTextMarker;
+
 /// Spawn a light gray rectangle at the bottom of the screen to cover the bottom padding
 fn create_ui(mut commands: Commands, assets: Res<crate::assets::MiscAssets>) {
     commands
@@ -40,6 +74,7 @@ fn create_ui(mut commands: Commands, assets: Res<crate::assets::MiscAssets>) {
             color: Color::rgb(0.3, 0.3, 0.3).into(),
             ..Default::default()
         })
+        .insert(BottomBarMarker)
         .insert(crate::RemoveOnGameplayExit)
         .add_children(|parent| {
             // Turn icons
@@ -62,10 +97,143 @@ fn create_ui(mut commands: Commands, assets: Res<crate::assets::MiscAssets>) {
                 })
                 .insert(TurnIconMarker)
                 .insert(crate::RemoveOnGameplayExit);
+
+            // Gold and health
+            let text_style = TextStyle {
+                font: assets.font.clone_weak(),
+                font_size: 20.,
+                color: Color::WHITE,
+            };
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![
+                            TextSection {
+                                value: String::new(),
+                                style: text_style.clone(),
+                            },
+                            TextSection {
+                                value: String::new(),
+                                style: text_style.clone(),
+                            },
+                            TextSection {
+                                value: String::new(),
+                                style: TextStyle {
+                                    font_size: 12.,
+                                    ..text_style.clone()
+                                },
+                            },
+                            TextSection {
+                                value: String::new(),
+                                style: TextStyle {
+                                    font_size: 12.,
+                                    ..text_style.clone()
+                                },
+                            },
+                        ],
+                        ..default()
+                    },
+                    style: Style {
+                        margin: UiRect {
+                            left: Val::Px(16.),
+                            ..default()
+                        },
+                        align_self: AlignSelf::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(StatsTextMarker);
+
+            // End turn
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(96.), Val::Px(48.)),
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            right: Val::Px(16.),
+                            bottom: Val::Px(16.),
+                            ..default()
+                        },
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                })
+                .insert(EndTurnButtonMarker)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle::from_section(
+                            "End Turn",
+                            TextStyle {
+                                font_size: 16.,
+                                ..text_style.clone()
+                            },
+                        ))
+                        .insert(EndTurnTextMarker);
+                });
+
+            // Attack mode
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(96.), Val::Px(32.)),
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            right: Val::Px(16.),
+                            bottom: Val::Px(72.),
+                            ..default()
+                        },
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                })
+                .insert(AttackModeButtonMarker)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 12.,
+                                ..text_style
+                            },
+                        ))
+                        .insert(AttackModeTextMarker);
+                });
         });
 
-        // We are gonna fake the other UI elements using world space (since our camera doesnt move)
-        
+    // Shown once every wave is beaten
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "All waves cleared!",
+                TextStyle {
+                    font: assets.font.clone_weak(),
+                    font_size: 32.,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(16.),
+                    left: Val::Px(16.),
+                    ..default()
+                },
+                display: Display::None,
+                ..default()
+            }),
+        )
+        .insert(WavesClearedTextMarker)
+        .insert(crate::RemoveOnGameplayExit);
+
+    // We are gonna fake the other UI elements using world space (since our camera doesnt move)
 }
 
 /// Set turn icon
@@ -91,4 +259,118 @@ fn set_turn_icon(
         let mut ui_atlas = query.single_mut();
         ui_atlas.index = img_index;
     }
-}
\ No newline at end of file
+}
+
+/// Show the players gold and health, how far ahead enemy moves are previewed and the endless run
+fn update_stats_text(
+    gold: Res<PlayerGold>,
+    health: Res<PlayerHealth>,
+    preview: Res<MovePreviewTurns>,
+    endless: Res<EndlessMode>,
+    run: Res<EndlessRun>,
+    mut query: Query<&mut Text, With<StatsTextMarker>>,
+) {
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!("Gold: {}\n", gold.0);
+        text.sections[1].value = format!("Health: {}\n", health.0);
+        text.sections[2].value = match preview.0 {
+            0 => "Preview: off (P)\n".to_owned(),
+            1 => "Preview: 1 turn (P)\n".to_owned(),
+            turns => format!("Preview: {turns} turns (P)\n"),
+        };
+        // Endless mode can only be toggled before the first turn ends
+        let hint = if run.locked { "" } else { " (E)" };
+        text.sections[3].value = if endless.enabled {
+            format!(
+                "Endless: turn {}, best {}, seed {:X}{hint}",
+                run.turns, run.best, endless.seed
+            )
+        } else {
+            format!("Endless: off, best {}{hint}", run.best)
+        };
+    }
+}
+
+/// Tell the player when every wave is cleared
+fn show_waves_cleared(
+    cleared: Res<WavesCleared>,
+    mut query: Query<&mut Style, With<WavesClearedTextMarker>>,
+) {
+    if !cleared.is_changed() {
+        return;
+    }
+
+    for mut style in query.iter_mut() {
+        style.display = if cleared.0 {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+/// End the player action part of the turn when the end turn button is clicked
+///
+/// When attacking manually the same button fires the queued attacks
+fn end_turn_button(
+    mut query: Query<(&Interaction, &mut UiColor), With<EndTurnButtonMarker>>,
+    clicked_query: Query<&Interaction, (Changed<Interaction>, With<EndTurnButtonMarker>)>,
+    mut text_query: Query<&mut Text, With<EndTurnTextMarker>>,
+    current_state: Res<CurrentState<TurnState>>,
+    attack_mode: Res<AttackMode>,
+    mut end_turn: ResMut<EndTurnRequested>,
+) {
+    let (can_end_turn, label) = match current_state.0 {
+        TurnState::InTurn(TurnPart::PlayerAction) => (true, "End Turn"),
+        TurnState::InTurn(TurnPart::PlayerAttack) => (*attack_mode == AttackMode::Manual, "Fire"),
+        _ => (false, "End Turn"),
+    };
+
+    // Only request once per click, not on every frame the button is held down
+    for interaction in clicked_query.iter() {
+        if can_end_turn && *interaction == Interaction::Clicked {
+            end_turn.0 = true;
+        }
+    }
+    for (interaction, mut color) in query.iter_mut() {
+        color.0 = button_color(can_end_turn, *interaction);
+    }
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.to_owned();
+        }
+    }
+}
+
+/// Switch between automatic and manual attacks when the attack mode button is clicked
+fn attack_mode_button(
+    mut query: Query<(&Interaction, &mut UiColor), With<AttackModeButtonMarker>>,
+    clicked_query: Query<&Interaction, (Changed<Interaction>, With<AttackModeButtonMarker>)>,
+    mut text_query: Query<&mut Text, With<AttackModeTextMarker>>,
+    mut attack_mode: ResMut<AttackMode>,
+) {
+    // Only toggle when the button gets clicked, not on every frame it is held down
+    for interaction in clicked_query.iter() {
+        if *interaction == Interaction::Clicked {
+            *attack_mode = attack_mode.toggled();
+        }
+    }
+    for (interaction, mut color) in query.iter_mut() {
+        color.0 = button_color(true, *interaction);
+    }
+    let label = format!("{} [M]", attack_mode.name());
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+/// Color of a button in the bottom bar
+pub fn button_color(available: bool, interaction: Interaction) -> Color {
+    match (available, interaction) {
+        (false, _) => Color::rgb(0.2, 0.2, 0.2),
+        (true, Interaction::None) => Color::rgb(0.4, 0.4, 0.4),
+        (true, _) => Color::rgb(0.5, 0.5, 0.5),
+    }
+}
@@ -31,13 +31,19 @@ pub fn is_animation_done<T: Component>(
 
 /// Get a field from a entity!
 pub fn get_field<'a>(entity: &'a EntityInstance, field_name: &str) -> &'a FieldValue {
-    for field in &entity.field_instances {
-        if field.identifier == field_name {
-            return &field.value;
-        }
-    }
+    find_field(entity, field_name)
+        .unwrap_or_else(|| panic!("could not find field value with name {field_name}"))
+}
 
-    panic!("could not find field value with name {field_name}");
+/// Get a field from a entity, if the entity has it
+///
+/// Entities placed before a field was added to the level dont have it until they are saved again
+pub fn find_field<'a>(entity: &'a EntityInstance, field_name: &str) -> Option<&'a FieldValue> {
+    entity
+        .field_instances
+        .iter()
+        .find(|field| field.identifier == field_name)
+        .map(|field| &field.value)
 }
 
 /// extracts a value from a pattern, panicing if it doesnt match